@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::{history_db, migrations, AppData, DownloadHistoryEntry};
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    // Adds to what's here. Local settings win; rules with the same id are replaced.
+    #[default]
+    Merge,
+    // Makes this machine match the file exactly
+    Replace,
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportSummary {
+    pub history_added: usize,
+    pub history_skipped: usize,
+    // Entries left out because their timestamp isn't "YYYY-MM-DD HH:MM:SS"
+    pub history_invalid: usize,
+    pub rules_added: usize,
+    pub rules_replaced: usize,
+    // Imported rules that trash files come in switched off, to be turned on by hand
+    pub rules_disabled: usize,
+    pub recent_destinations: usize,
+}
+
+// The whole AppData, with the history pulled back in from history.db
+pub fn export_json(path: &Path) -> Result<(), String> {
+    let mut data = crate::load_app_data()?;
+    data.schema_version = migrations::CURRENT_VERSION;
+    data.download_history = history_db::all()?;
+
+    let json = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Quotes a field only when it needs it, doubling any quotes inside
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn history_csv(entries: &[DownloadHistoryEntry]) -> String {
+    let mut csv = String::from("timestamp,action,name,original_path,size,destination,detected_at\n");
+    for entry in entries {
+        let fields = [
+            csv_field(&entry.timestamp),
+            csv_field(&entry.action),
+            csv_field(&entry.name),
+            csv_field(&entry.original_path),
+            entry.size.to_string(),
+            csv_field(entry.destination.as_deref().unwrap_or("")),
+            csv_field(entry.detected_at.as_deref().unwrap_or("")),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn export_history_csv(path: &Path) -> Result<(), String> {
+    let csv = history_csv(&history_db::all()?);
+    fs::write(path, csv).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Anything exported by an older version is upgraded the same way data.json is
+fn read_export(path: &Path) -> Result<AppData, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut value: serde_json::Value =
+        serde_json::from_str(&contents).map_err(|e| format!("{} is not valid JSON: {}", path.display(), e))?;
+    migrations::migrate(&mut value)?;
+    serde_json::from_value(value).map_err(|e| format!("{} is not a FileForge export: {}", path.display(), e))
+}
+
+fn merge_into(data: &mut AppData, mut incoming: AppData, mode: ImportMode, summary: &mut ImportSummary) {
+    for rule in &mut incoming.rules {
+        if rule.enabled && matches!(rule.action, crate::rules::RuleAction::Trash) {
+            rule.enabled = false;
+            summary.rules_disabled += 1;
+        }
+    }
+
+    match mode {
+        ImportMode::Replace => {
+            summary.rules_added = incoming.rules.len();
+            data.rules = incoming.rules;
+            data.settings = incoming.settings;
+            data.recent_destinations = incoming.recent_destinations;
+        }
+        ImportMode::Merge => {
+            for rule in incoming.rules {
+                match data.rules.iter_mut().find(|r| r.id == rule.id) {
+                    Some(existing) => {
+                        *existing = rule;
+                        summary.rules_replaced += 1;
+                    }
+                    None => {
+                        data.rules.push(rule);
+                        summary.rules_added += 1;
+                    }
+                }
+            }
+            for destination in incoming.recent_destinations {
+                if !data.recent_destinations.contains(&destination) {
+                    data.recent_destinations.push(destination);
+                }
+            }
+        }
+    }
+    data.recent_destinations.truncate(data.settings.recent_destinations_limit);
+    summary.recent_destinations = data.recent_destinations.len();
+}
+
+// Timestamps name archive files and drive the stats, so a shared file only gets in
+// entries whose timestamp is one this app would have written
+fn valid_timestamp(timestamp: &str) -> bool {
+    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S").is_ok()
+}
+
+pub fn import_json(path: &Path, mode: ImportMode) -> Result<ImportSummary, String> {
+    let mut incoming = read_export(path)?;
+    let (history, invalid): (Vec<_>, Vec<_>) = std::mem::take(&mut incoming.download_history)
+        .into_iter()
+        .partition(|entry| valid_timestamp(&entry.timestamp));
+
+    let previous = crate::load_app_data()?;
+    let mut data = previous.clone();
+    let mut summary = ImportSummary::default();
+    merge_into(&mut data, incoming, mode, &mut summary);
+
+    // Settings and rules are saved while the history can still roll back, so a failure
+    // leaves neither imported. Should the history commit fail after that, they go back.
+    let saved = std::cell::Cell::new(false);
+    let imported = history_db::import(&history, mode == ImportMode::Replace, &data.settings.history, || {
+        crate::save_app_data(&data)?;
+        saved.set(true);
+        Ok(())
+    });
+    if imported.is_err() && saved.get() {
+        if let Err(e) = crate::save_app_data(&previous) {
+            eprintln!("Could not put the settings from before the import back: {}", e);
+        }
+    }
+    let (added, skipped) = imported?;
+    summary.history_added = added;
+    summary.history_skipped = skipped;
+    summary.history_invalid = invalid.len();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, name: &str) -> crate::rules::Rule {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": name,
+            "conditions": [],
+            "action": { "type": "keep" }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_keeps_local_settings_and_replaces_rules_by_id() {
+        let mut data = AppData {
+            recent_destinations: vec!["/a".to_string(), "/b".to_string()],
+            rules: vec![rule("pdfs", "Old PDFs")],
+            ..Default::default()
+        };
+        data.settings.recent_destinations_limit = 3;
+
+        let mut incoming = AppData {
+            recent_destinations: vec!["/b".to_string(), "/c".to_string(), "/d".to_string()],
+            rules: vec![rule("pdfs", "New PDFs"), rule("zips", "Zips")],
+            ..Default::default()
+        };
+        incoming.settings.recent_destinations_limit = 10;
+
+        let mut summary = ImportSummary::default();
+        merge_into(&mut data, incoming, ImportMode::Merge, &mut summary);
+
+        assert_eq!(data.settings.recent_destinations_limit, 3);
+        assert_eq!(data.recent_destinations, ["/a", "/b", "/c"]);
+        assert_eq!(data.rules.len(), 2);
+        assert_eq!(data.rules[0].name, "New PDFs");
+        assert_eq!((summary.rules_added, summary.rules_replaced), (1, 1));
+    }
+
+    #[test]
+    fn imported_trash_rules_come_in_switched_off() {
+        let mut data = AppData::default();
+        let mut trash = rule("old", "Old installers");
+        trash.action = crate::rules::RuleAction::Trash;
+        let incoming = AppData {
+            rules: vec![trash, rule("zips", "Zips")],
+            ..Default::default()
+        };
+
+        let mut summary = ImportSummary::default();
+        merge_into(&mut data, incoming, ImportMode::Replace, &mut summary);
+
+        let enabled: Vec<bool> = data.rules.iter().map(|r| r.enabled).collect();
+        assert_eq!(enabled, [false, true]);
+        assert_eq!(summary.rules_disabled, 1);
+    }
+
+    #[test]
+    fn only_well_formed_timestamps_are_imported() {
+        assert!(valid_timestamp("2026-01-05 09:00:00"));
+        for timestamp in ["../../x", "2026-01-05", "2026-13-05 09:00:00", "2026-01-05T09:00:00", "2026-01-05 09:00:00/../x", ""] {
+            assert!(!valid_timestamp(timestamp), "{}", timestamp);
+        }
+    }
+
+    #[test]
+    fn csv_quotes_awkward_fields() {
+        let entry = DownloadHistoryEntry {
+            name: "report, \"final\".pdf".to_string(),
+            original_path: "/downloads/report.pdf".to_string(),
+            size: 42,
+            timestamp: "2026-01-05 09:00:00".to_string(),
+            action: "moved".to_string(),
+            destination: Some("/docs".to_string()),
+            trash_id: None,
+            detected_at: None,
+            content_hash: None,
+        };
+        let csv = history_csv(&[entry]);
+        assert_eq!(
+            csv.lines().nth(1).unwrap(),
+            "2026-01-05 09:00:00,moved,\"report, \"\"final\"\".pdf\",/downloads/report.pdf,42,/docs,"
+        );
+    }
+}
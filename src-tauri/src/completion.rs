@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+// What browsers call a download until it's done. Chrome, Firefox and Safari rename
+// the file to its real name at the end.
+const TEMP_SUFFIXES: &[&str] = &[".crdownload", ".part", ".partial", ".download", ".tmp"];
+// Reported files remembered, so later events for the same unchanged file stay quiet
+const REMEMBERED_FINISHED: usize = 256;
+
+// The real name of a download in progress, e.g. report.pdf for report.pdf.crdownload
+pub fn temp_target(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    let lower = name.to_lowercase();
+    let suffix = TEMP_SUFFIXES.iter().find(|s| lower.ends_with(*s))?;
+    let real = &name[..name.len() - suffix.len()];
+    (!real.is_empty()).then(|| path.with_file_name(real))
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Snapshot {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+// How the tracker looks at files, so tests can fake the disk
+pub trait Probe {
+    // None once the path is gone or isn't a regular file
+    fn snapshot(&self, path: &Path) -> Option<Snapshot>;
+    fn open_for_writing(&self, path: &Path) -> bool;
+}
+
+pub struct Disk;
+
+impl Probe for Disk {
+    fn snapshot(&self, path: &Path) -> Option<Snapshot> {
+        let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+        Some(Snapshot {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    fn open_for_writing(&self, path: &Path) -> bool {
+        open_for_writing(path)
+    }
+}
+
+// Any process with a write (or read-write) descriptor on the file, found through /proc.
+// Processes of other users can't be inspected, which is fine for a Downloads folder.
+#[cfg(target_os = "linux")]
+fn open_for_writing(path: &Path) -> bool {
+    let Ok(target) = fs::canonicalize(path) else { return false };
+    let Ok(processes) = fs::read_dir("/proc") else { return false };
+
+    for process in processes.flatten() {
+        let Ok(fds) = fs::read_dir(process.path().join("fd")) else { continue };
+        for fd in fds.flatten() {
+            if fs::read_link(fd.path()).ok().as_deref() != Some(target.as_path()) {
+                continue;
+            }
+            // "flags:" is octal; the low two bits are O_WRONLY (1) or O_RDWR (2)
+            let info = fs::read_to_string(process.path().join("fdinfo").join(fd.file_name())).unwrap_or_default();
+            let flags = info
+                .lines()
+                .find_map(|line| line.strip_prefix("flags:"))
+                .and_then(|flags| u32::from_str_radix(flags.trim(), 8).ok());
+            if flags.is_some_and(|flags| flags & 0o3 != 0) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+// Opening without sharing fails while any other handle is open. That also catches
+// readers (virus scanners), which is no bad thing before moving the file.
+#[cfg(windows)]
+fn open_for_writing(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    fs::OpenOptions::new()
+        .read(true)
+        .share_mode(0)
+        .open(path)
+        .is_err_and(|e| e.raw_os_error() == Some(ERROR_SHARING_VIOLATION))
+}
+
+// No cheap way to ask elsewhere, so the stability window has to do
+#[cfg(not(any(target_os = "linux", windows)))]
+fn open_for_writing(_path: &Path) -> bool {
+    false
+}
+
+struct Candidate {
+    detected_at: String,
+    last: Option<Snapshot>,
+    stable_since: Instant,
+}
+
+impl Candidate {
+    fn new(now: Instant) -> Self {
+        Candidate {
+            detected_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            last: None,
+            stable_since: now,
+        }
+    }
+}
+
+pub struct Finished {
+    pub path: PathBuf,
+    pub size: u64,
+    // When the first file of its rename chain showed up
+    pub detected_at: String,
+}
+
+// Every file that might be a new download, each with its own clock, so a slow
+// download never holds up one that has already finished.
+pub struct CompletionTracker {
+    candidates: HashMap<PathBuf, Candidate>,
+    window: Duration,
+    // Newest last, with what the file looked like when it was reported
+    finished: VecDeque<(PathBuf, Snapshot)>,
+}
+
+impl CompletionTracker {
+    pub fn new(window: Duration) -> Self {
+        CompletionTracker {
+            candidates: HashMap::new(),
+            window,
+            finished: VecDeque::new(),
+        }
+    }
+
+    // How long size and modified time must stay put before a file counts as done
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+    }
+
+    pub fn observe(&mut self, path: PathBuf, now: Instant) {
+        self.candidates.entry(path).or_insert_with(|| Candidate::new(now));
+    }
+
+    // Follows the download from its temporary name to the real one, keeping when it
+    // was first seen. Firefox leaves an empty placeholder under the real name; the
+    // renamed file simply takes its place.
+    pub fn rename(&mut self, from: &Path, to: PathBuf, now: Instant) {
+        let Some(mut candidate) = self.candidates.remove(from) else {
+            // Renaming a file that was already reported doesn't make it new
+            if let Some(entry) = self.finished.iter_mut().find(|(path, _)| path == from) {
+                entry.0 = to.clone();
+            }
+            self.observe(to, now);
+            return;
+        };
+        if let Some(placeholder) = self.candidates.remove(&to) {
+            candidate.detected_at = candidate.detected_at.min(placeholder.detected_at);
+        }
+        candidate.last = None;
+        candidate.stable_since = now;
+        self.candidates.insert(to, candidate);
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        self.candidates.remove(path);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    // Returns the files that are done, and stops tracking them and anything that vanished
+    pub fn poll(&mut self, now: Instant, probe: &impl Probe) -> Vec<Finished> {
+        // Real names that an in-progress download will take over
+        let in_progress: HashSet<PathBuf> = self.candidates.keys().filter_map(|p| temp_target(p)).collect();
+        let window = self.window;
+        let reported = &self.finished;
+        let mut finished = Vec::new();
+
+        self.candidates.retain(|path, candidate| {
+            let Some(snapshot) = probe.snapshot(path) else {
+                return false;
+            };
+            if candidate.last != Some(snapshot) {
+                candidate.last = Some(snapshot);
+                candidate.stable_since = now;
+                return true;
+            }
+            // A paused download sits still under its temporary name, so that name never finishes
+            if temp_target(path).is_some() || in_progress.contains(path) {
+                return true;
+            }
+            if now.duration_since(candidate.stable_since) < window || probe.open_for_writing(path) {
+                return true;
+            }
+            if !reported.iter().any(|(p, s)| p == path && *s == snapshot) {
+                finished.push((
+                    Finished {
+                        path: path.clone(),
+                        size: snapshot.size,
+                        detected_at: candidate.detected_at.clone(),
+                    },
+                    snapshot,
+                ));
+            }
+            false
+        });
+
+        for (done, snapshot) in &finished {
+            self.finished.retain(|(path, _)| *path != done.path);
+            self.finished.push_back((done.path.clone(), *snapshot));
+        }
+        while self.finished.len() > REMEMBERED_FINISHED {
+            self.finished.pop_front();
+        }
+        finished.into_iter().map(|(done, _)| done).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct FakeDisk {
+        files: RefCell<HashMap<PathBuf, u64>>,
+        writing: RefCell<HashSet<PathBuf>>,
+    }
+
+    impl FakeDisk {
+        fn write(&self, path: &str, size: u64) {
+            self.files.borrow_mut().insert(PathBuf::from(path), size);
+        }
+
+        fn rename(&self, from: &str, to: &str) {
+            let size = self.files.borrow_mut().remove(Path::new(from)).unwrap();
+            self.write(to, size);
+        }
+    }
+
+    impl Probe for FakeDisk {
+        fn snapshot(&self, path: &Path) -> Option<Snapshot> {
+            self.files.borrow().get(path).map(|&size| Snapshot { size, modified: None })
+        }
+
+        fn open_for_writing(&self, path: &Path) -> bool {
+            self.writing.borrow().contains(path)
+        }
+    }
+
+    fn names(finished: &[Finished]) -> Vec<String> {
+        finished.iter().map(|f| f.path.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn strips_temp_suffixes() {
+        assert_eq!(temp_target(Path::new("d/report.pdf.crdownload")), Some(PathBuf::from("d/report.pdf")));
+        assert_eq!(temp_target(Path::new("d/movie.mkv.PART")), Some(PathBuf::from("d/movie.mkv")));
+        assert_eq!(temp_target(Path::new("d/report.pdf")), None);
+        assert_eq!(temp_target(Path::new("d/.part")), None);
+    }
+
+    #[test]
+    fn follows_rename_chain_and_waits_for_stability() {
+        let disk = FakeDisk::default();
+        let mut tracker = CompletionTracker::new(Duration::from_secs(2));
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // Firefox: empty placeholder plus the .part file that fills up
+        disk.write("d/big.iso", 0);
+        disk.write("d/big.iso.part", 10);
+        tracker.observe(PathBuf::from("d/big.iso"), at(0));
+        tracker.observe(PathBuf::from("d/big.iso.part"), at(0));
+        // A small file finishes on its own while the big one is still going
+        disk.write("d/small.txt", 5);
+        tracker.observe(PathBuf::from("d/small.txt"), at(0));
+
+        assert!(tracker.poll(at(0), &disk).is_empty());
+        disk.write("d/big.iso.part", 20);
+        assert!(tracker.poll(at(1), &disk).is_empty());
+        assert_eq!(names(&tracker.poll(at(3), &disk)), ["d/small.txt"]);
+
+        // The placeholder doesn't finish while its .part is around, however long it sits
+        assert!(tracker.poll(at(10), &disk).is_empty());
+
+        disk.rename("d/big.iso.part", "d/big.iso");
+        tracker.rename(Path::new("d/big.iso.part"), PathBuf::from("d/big.iso"), at(11));
+        assert!(tracker.poll(at(11), &disk).is_empty());
+
+        disk.writing.borrow_mut().insert(PathBuf::from("d/big.iso"));
+        assert!(tracker.poll(at(14), &disk).is_empty());
+        disk.writing.borrow_mut().clear();
+
+        let finished = tracker.poll(at(15), &disk);
+        assert_eq!(names(&finished), ["d/big.iso"]);
+        assert_eq!(finished[0].size, 20);
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn drops_files_deleted_before_finishing() {
+        let disk = FakeDisk::default();
+        let mut tracker = CompletionTracker::new(Duration::from_secs(1));
+        let start = Instant::now();
+
+        disk.write("d/cancelled.zip.crdownload", 100);
+        tracker.observe(PathBuf::from("d/cancelled.zip.crdownload"), start);
+        assert!(tracker.poll(start, &disk).is_empty());
+        disk.files.borrow_mut().clear();
+        assert!(tracker.poll(start + Duration::from_secs(5), &disk).is_empty());
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn reports_each_finished_file_once() {
+        let disk = FakeDisk::default();
+        let mut tracker = CompletionTracker::new(Duration::from_secs(1));
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        disk.write("d/a.pdf", 10);
+        tracker.observe(PathBuf::from("d/a.pdf"), at(0));
+        tracker.poll(at(0), &disk);
+        assert_eq!(names(&tracker.poll(at(2), &disk)), ["d/a.pdf"]);
+
+        // Seen again unchanged, or renamed by the user: nothing new
+        tracker.observe(PathBuf::from("d/a.pdf"), at(3));
+        tracker.poll(at(3), &disk);
+        assert!(tracker.poll(at(5), &disk).is_empty());
+        disk.rename("d/a.pdf", "d/b.pdf");
+        tracker.rename(Path::new("d/a.pdf"), PathBuf::from("d/b.pdf"), at(6));
+        tracker.poll(at(6), &disk);
+        assert!(tracker.poll(at(8), &disk).is_empty());
+
+        // Overwritten with different content: a new download under the same name
+        disk.write("d/b.pdf", 20);
+        tracker.observe(PathBuf::from("d/b.pdf"), at(9));
+        tracker.poll(at(9), &disk);
+        assert_eq!(names(&tracker.poll(at(11), &disk)), ["d/b.pdf"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sees_open_write_handles() {
+        let path = std::env::temp_dir().join(format!("fileforge-open-{}", std::process::id()));
+        let file = fs::File::create(&path).unwrap();
+        assert!(open_for_writing(&path));
+        drop(file);
+        assert!(!open_for_writing(&path));
+        fs::remove_file(&path).unwrap();
+    }
+}
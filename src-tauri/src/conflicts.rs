@@ -0,0 +1,366 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::journal::Operation;
+
+// What to do when the destination of a move/copy already exists
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Fail,
+    Skip,
+    Overwrite,
+    // "report.pdf" becomes "report (1).pdf"
+    KeepBoth,
+    KeepNewer,
+    KeepLarger,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    // Destination is free - possibly a renamed "(1)" path
+    Proceed(PathBuf),
+    // Destination exists and should be replaced
+    Replace(PathBuf),
+    Skip,
+}
+
+#[derive(Serialize)]
+pub struct ConflictInfo {
+    pub source: String,
+    pub destination: String,
+    pub reason: String,
+    pub source_size: u64,
+    pub destination_size: Option<u64>,
+    // Seconds since the Unix epoch
+    pub source_modified: Option<u64>,
+    pub destination_modified: Option<u64>,
+    // What keep-both would name the file
+    pub suggested_destination: String,
+}
+
+fn modified_secs(path: &Path) -> Option<u64> {
+    fs::symlink_metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
+fn size_of(path: &Path) -> u64 {
+    crate::transfer::tree_size(path)
+}
+
+fn exists(path: &Path) -> bool {
+    // symlink_metadata so a dangling link still counts as taken
+    fs::symlink_metadata(path).is_ok()
+}
+
+// First free "name (n).ext" next to `destination`
+pub fn next_free_name(destination: &Path) -> PathBuf {
+    next_free_name_in(destination, &HashSet::new())
+}
+
+// Names that differ only in case are one file on Windows and macOS, two on Linux
+fn name_key(name: &OsStr) -> String {
+    crate::rules::destination_key(&name.to_string_lossy())
+}
+
+// Like next_free_name, also passing over names already `reserved` (as name_key keys)
+fn next_free_name_in(destination: &Path, reserved: &HashSet<String>) -> PathBuf {
+    let parent = destination.parent().map(Path::to_path_buf).unwrap_or_default();
+    let is_dir = destination.is_dir();
+    let name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // Folders keep their dots, "my.project (1)" rather than "my (1).project"
+    let (stem, extension) = match (is_dir, destination.extension()) {
+        (false, Some(ext)) => (
+            destination
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            format!(".{}", ext.to_string_lossy()),
+        ),
+        _ => (name, String::new()),
+    };
+
+    (1..)
+        .map(|n| parent.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| {
+            !exists(candidate) && !candidate.file_name().is_some_and(|n| reserved.contains(&name_key(n)))
+        })
+        .unwrap_or_else(|| destination.to_path_buf())
+}
+
+// Unused hidden name beside `destination`, where a replacement waits until the old item is gone
+pub fn staging_path(destination: &Path) -> PathBuf {
+    let parent = destination.parent().map(Path::to_path_buf).unwrap_or_default();
+    let name = destination
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    (0..)
+        .map(|n| parent.join(format!(".{}.fileforge-replace-{}", name, n)))
+        .find(|candidate| !exists(candidate))
+        .unwrap_or_else(|| destination.to_path_buf())
+}
+
+// Trashes `destination` and renames `staged` into its place, returning the journal entry
+// for the trashed item. If the rename fails the old item comes back out of the trash.
+pub fn swap_into_place(staged: &Path, destination: &Path) -> Result<Operation, String> {
+    trash::delete(destination).map_err(|e| format!("Cannot replace {}: {}", destination.display(), e))?;
+    let trashed = Operation::Delete {
+        path: destination.to_string_lossy().to_string(),
+        trash_id: crate::trash_bin::find_trash_id(destination),
+    };
+
+    if let Err(e) = fs::rename(staged, destination) {
+        restore_replaced(&trashed);
+        return Err(format!("Cannot replace {}: {}", destination.display(), e));
+    }
+    Ok(trashed)
+}
+
+// Puts an item trashed by swap_into_place back. Whatever took its place must be gone already.
+pub fn restore_replaced(trashed: &Operation) {
+    let Operation::Delete { path, trash_id } = trashed else {
+        return;
+    };
+    let restored = match trash_id {
+        Some(id) => crate::trash_bin::restore(id),
+        None => Err("it was not found in the trash".to_string()),
+    };
+    if let Err(e) = restored {
+        eprintln!("Could not put {} back: {}", path, e);
+    }
+}
+
+pub fn resolve(source: &Path, destination: &Path, policy: ConflictPolicy) -> Result<Resolution, String> {
+    if !exists(destination) {
+        return Ok(Resolution::Proceed(destination.to_path_buf()));
+    }
+
+    if let (Ok(a), Ok(b)) = (fs::canonicalize(source), fs::canonicalize(destination)) {
+        if a == b {
+            return Err("Source and destination are the same".to_string());
+        }
+    }
+
+    let replace_if = |wins: bool| {
+        if wins {
+            Resolution::Replace(destination.to_path_buf())
+        } else {
+            Resolution::Skip
+        }
+    };
+
+    match policy {
+        ConflictPolicy::Fail => Err(format!("{} already exists", destination.display())),
+        ConflictPolicy::Skip => Ok(Resolution::Skip),
+        ConflictPolicy::Overwrite => Ok(Resolution::Replace(destination.to_path_buf())),
+        ConflictPolicy::KeepBoth => Ok(Resolution::Proceed(next_free_name(destination))),
+        ConflictPolicy::KeepNewer => Ok(replace_if(modified_secs(source) > modified_secs(destination))),
+        ConflictPolicy::KeepLarger => Ok(replace_if(size_of(source) > size_of(destination))),
+    }
+}
+
+// Pre-flight for a whole multi-select batch going into one folder
+pub fn check(sources: &[String], destination_folder: &Path) -> Vec<ConflictInfo> {
+    let mut conflicts = Vec::new();
+    let mut names_in_batch: HashSet<String> = HashSet::new();
+
+    for source in sources {
+        let source_path = Path::new(source);
+        let name = match source_path.file_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        let destination = destination_folder.join(name);
+
+        let reason = if exists(&destination) {
+            "Destination already exists"
+        } else if !names_in_batch.insert(name_key(name)) {
+            "Another selected item has the same name"
+        } else {
+            continue;
+        };
+
+        // Two clashing items never get the same suggestion
+        let suggested = next_free_name_in(&destination, &names_in_batch);
+        if let Some(suggested_name) = suggested.file_name() {
+            names_in_batch.insert(name_key(suggested_name));
+        }
+
+        let destination_exists = exists(&destination);
+        conflicts.push(ConflictInfo {
+            source: source.clone(),
+            destination: destination.to_string_lossy().to_string(),
+            reason: reason.to_string(),
+            source_size: size_of(source_path),
+            destination_size: destination_exists.then(|| size_of(&destination)),
+            source_modified: modified_secs(source_path),
+            destination_modified: modified_secs(&destination),
+            suggested_destination: suggested.to_string_lossy().to_string(),
+        });
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+
+    fn scratch(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fileforge-conflicts-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("from")).unwrap();
+        fs::create_dir_all(root.join("to")).unwrap();
+        root
+    }
+
+    fn set_mtime(path: &Path, secs: i64) {
+        filetime::set_file_mtime(path, FileTime::from_unix_time(secs, 0)).unwrap();
+    }
+
+    #[test]
+    fn free_destination_just_proceeds_and_default_fails() {
+        let root = scratch("default");
+        let (source, target) = (root.join("from/a.pdf"), root.join("to/a.pdf"));
+        fs::write(&source, "new").unwrap();
+
+        assert_eq!(resolve(&source, &target, ConflictPolicy::default()), Ok(Resolution::Proceed(target.clone())));
+        fs::write(&target, "old").unwrap();
+        assert!(resolve(&source, &target, ConflictPolicy::default()).is_err());
+        assert_eq!(resolve(&source, &target, ConflictPolicy::Skip), Ok(Resolution::Skip));
+        // Moving a file onto itself is never a conflict to resolve
+        assert!(resolve(&target, &target, ConflictPolicy::Overwrite).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn keep_both_numbers_past_taken_names() {
+        let root = scratch("numbering");
+        let to = root.join("to");
+        for name in ["report.pdf", "report (1).pdf", "README", ".bashrc", "archive.tar.gz"] {
+            fs::write(to.join(name), "").unwrap();
+        }
+        fs::create_dir(to.join("my.project")).unwrap();
+
+        assert_eq!(next_free_name(&to.join("report.pdf")), to.join("report (2).pdf"));
+        assert_eq!(next_free_name(&to.join("README")), to.join("README (1)"));
+        assert_eq!(next_free_name(&to.join(".bashrc")), to.join(".bashrc (1)"));
+        assert_eq!(next_free_name(&to.join("archive.tar.gz")), to.join("archive.tar (1).gz"));
+        // Folders keep their dots
+        assert_eq!(next_free_name(&to.join("my.project")), to.join("my.project (1)"));
+
+        let source = root.join("from/report.pdf");
+        fs::write(&source, "").unwrap();
+        assert_eq!(
+            resolve(&source, &to.join("report.pdf"), ConflictPolicy::KeepBoth),
+            Ok(Resolution::Proceed(to.join("report (2).pdf")))
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn keep_newer_and_larger_skip_on_ties() {
+        let root = scratch("ties");
+        let (source, target) = (root.join("from/a.bin"), root.join("to/a.bin"));
+        fs::write(&source, "1234").unwrap();
+        fs::write(&target, "5678").unwrap();
+        set_mtime(&source, 1_000_000);
+        set_mtime(&target, 1_000_000);
+
+        assert_eq!(resolve(&source, &target, ConflictPolicy::KeepNewer), Ok(Resolution::Skip));
+        assert_eq!(resolve(&source, &target, ConflictPolicy::KeepLarger), Ok(Resolution::Skip));
+
+        set_mtime(&source, 2_000_000);
+        fs::write(&target, "567").unwrap();
+        set_mtime(&target, 1_000_000);
+        assert_eq!(resolve(&source, &target, ConflictPolicy::KeepNewer), Ok(Resolution::Replace(target.clone())));
+        assert_eq!(resolve(&source, &target, ConflictPolicy::KeepLarger), Ok(Resolution::Replace(target.clone())));
+        assert_eq!(resolve(&target, &source, ConflictPolicy::KeepNewer), Ok(Resolution::Skip));
+        assert_eq!(resolve(&target, &source, ConflictPolicy::KeepLarger), Ok(Resolution::Skip));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn overwrite_replaces_a_folder_too() {
+        let root = scratch("overwrite");
+        let (source, target) = (root.join("from/photos"), root.join("to/photos"));
+        fs::write(&source, "").unwrap();
+        fs::create_dir(&target).unwrap();
+
+        assert_eq!(resolve(&source, &target, ConflictPolicy::Overwrite), Ok(Resolution::Replace(target.clone())));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn check_reports_existing_and_clashing_names() {
+        let root = scratch("check");
+        let to = root.join("to");
+        fs::write(to.join("a.txt"), "old").unwrap();
+        let sources: Vec<String> = ["from/a.txt", "from/b.txt", "from/sub/b.txt", "from/other/B.TXT", "from/sub/a.txt"]
+            .iter()
+            .map(|p| root.join(p).to_string_lossy().to_string())
+            .collect();
+        fs::create_dir_all(root.join("from/sub")).unwrap();
+        fs::create_dir_all(root.join("from/other")).unwrap();
+        for source in &sources {
+            fs::write(source, "new!").unwrap();
+        }
+
+        let conflicts = check(&sources, &to);
+        let case_folds = cfg!(any(windows, target_os = "macos"));
+        assert_eq!(conflicts.len(), if case_folds { 4 } else { 3 });
+        assert_eq!(conflicts[0].reason, "Destination already exists");
+        assert_eq!(conflicts[0].destination_size, Some(3));
+        assert_eq!(conflicts[0].suggested_destination, to.join("a (1).txt").to_string_lossy());
+        assert_eq!(conflicts[1].source, sources[2]);
+        assert_eq!(conflicts[1].reason, "Another selected item has the same name");
+        assert_eq!(conflicts[1].destination_size, None);
+        assert_eq!(conflicts[1].suggested_destination, to.join("b (1).txt").to_string_lossy());
+        if case_folds {
+            assert_eq!(conflicts[2].source, sources[3]);
+            assert_eq!(conflicts[2].suggested_destination, to.join("B (2).TXT").to_string_lossy());
+        }
+
+        // A second item clashing with the same file gets the next suggestion along
+        let last = conflicts.last().unwrap();
+        assert_eq!(last.source, sources[4]);
+        assert_eq!(last.suggested_destination, to.join("a (2).txt").to_string_lossy());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn failed_replace_keeps_the_old_destination() {
+        let root = scratch("replace");
+        let target = root.join("to/a.txt");
+        fs::write(&target, "old").unwrap();
+
+        // The source is gone by the time the move runs
+        let missing = root.join("from/a.txt").to_string_lossy().to_string();
+        let moved = crate::move_file(missing, target.to_string_lossy().to_string(), None, Some(ConflictPolicy::Overwrite));
+        assert!(moved.is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(fs::read_dir(root.join("to")).unwrap().count(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
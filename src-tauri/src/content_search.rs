@@ -0,0 +1,308 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use walkdir::WalkDir;
+
+use crate::jobs::{JobControl, JobRegistry};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// Same heuristic as grep: a NUL byte near the start means binary
+const BINARY_SNIFF_LEN: usize = 8 * 1024;
+// Long minified lines are cut down so one hit can't flood the frontend
+const MAX_LINE_CHARS: usize = 300;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ContentQuery {
+    pub pattern: String,
+    // Otherwise the pattern is matched literally
+    pub regex: bool,
+    pub case_sensitive: bool,
+    // Bigger files are skipped without being read
+    pub max_file_size: u64,
+    // Lines of context on each side of a hit
+    pub context: usize,
+    // The search stops by itself after this many hits
+    pub max_hits: usize,
+}
+
+impl Default for ContentQuery {
+    fn default() -> Self {
+        ContentQuery {
+            pattern: String::new(),
+            regex: false,
+            case_sensitive: false,
+            max_file_size: 10 * 1024 * 1024,
+            context: 1,
+            max_hits: 1000,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct SearchHit {
+    pub job_id: String,
+    pub path: String,
+    // 1-based
+    pub line_number: usize,
+    pub line: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+struct SearchProgress {
+    job_id: String,
+    files_scanned: u64,
+    files_skipped: u64,
+    hits: usize,
+    state: String, // "running", "paused", "completed", "cancelled"
+    // Set once max_hits was reached
+    truncated: bool,
+}
+
+fn build_regex(query: &ContentQuery) -> Result<Regex, String> {
+    if query.pattern.is_empty() {
+        return Err("Search pattern is empty".to_string());
+    }
+    let pattern = if query.regex {
+        query.pattern.clone()
+    } else {
+        regex::escape(&query.pattern)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!query.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))
+}
+
+fn snippet(line: &str) -> String {
+    let line = line.trim_end_matches('\r');
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+// None for files that get skipped (too big, binary, unreadable)
+fn read_text(path: &Path, max_size: u64) -> Option<String> {
+    let metadata = fs::metadata(path).ok()?;
+    if metadata.len() > max_size {
+        return None;
+    }
+    let mut file = File::open(path).ok()?;
+    let mut bytes = Vec::with_capacity(metadata.len() as usize);
+    // Binaries are turned away after the first few KiB instead of being read in full
+    (&mut file).take(BINARY_SNIFF_LEN as u64).read_to_end(&mut bytes).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    // The size limit holds even if the file grew since the metadata was read
+    file.take(max_size.saturating_sub(bytes.len() as u64)).read_to_end(&mut bytes).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+// Calls `on_hit` for each matching line. Returns false if `on_hit` asked to stop.
+fn search_text(text: &str, regex: &Regex, context: usize, on_hit: &mut dyn FnMut(usize, String, Vec<String>, Vec<String>) -> bool) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+    for (index, line) in lines.iter().enumerate() {
+        if !regex.is_match(line) {
+            continue;
+        }
+        let before = lines[index.saturating_sub(context)..index].iter().map(|l| snippet(l)).collect();
+        let after = lines[index + 1..(index + 1 + context).min(lines.len())]
+            .iter()
+            .map(|l| snippet(l))
+            .collect();
+        if !on_hit(index + 1, snippet(line), before, after) {
+            return false;
+        }
+    }
+    true
+}
+
+struct Progress<'a> {
+    app: &'a AppHandle,
+    status: SearchProgress,
+    last_emit: Instant,
+}
+
+impl Progress<'_> {
+    fn emit(&mut self, state: &str) {
+        self.status.state = state.to_string();
+        let _ = self.app.emit("search-progress", self.status.clone());
+        self.last_emit = Instant::now();
+    }
+
+    fn tick(&mut self) {
+        if self.last_emit.elapsed() >= PROGRESS_INTERVAL {
+            self.emit("running");
+        }
+    }
+
+    // Returns false if the job was cancelled
+    fn checkpoint(&mut self, control: &JobControl) -> bool {
+        if control.is_paused() {
+            self.emit("paused");
+            let keep_going = control.checkpoint();
+            if keep_going {
+                self.emit("running");
+            }
+            return keep_going;
+        }
+        !control.is_cancelled()
+    }
+}
+
+// Returns false if cancelled
+fn run(root: &Path, query: &ContentQuery, regex: &Regex, control: &JobControl, progress: &mut Progress) -> bool {
+    let job_id = progress.status.job_id.clone();
+
+    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        if !progress.checkpoint(control) {
+            return false;
+        }
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let text = match read_text(entry.path(), query.max_file_size) {
+            Some(text) => text,
+            None => {
+                progress.status.files_skipped += 1;
+                continue;
+            }
+        };
+        progress.status.files_scanned += 1;
+
+        let path = entry.path().to_string_lossy().to_string();
+        let app = progress.app;
+        let status = &mut progress.status;
+        let finished_file = search_text(&text, regex, query.context, &mut |line_number, line, before, after| {
+            let _ = app.emit(
+                "search-hit",
+                SearchHit {
+                    job_id: job_id.clone(),
+                    path: path.clone(),
+                    line_number,
+                    line,
+                    before,
+                    after,
+                },
+            );
+            status.hits += 1;
+            status.hits < query.max_hits
+        });
+        if !finished_file {
+            progress.status.truncated = true;
+            return true;
+        }
+        progress.tick();
+    }
+    true
+}
+
+// Starts a background search and returns its job id right away. Hits arrive as
+// "search-hit" events, totals and the final state as "search-progress".
+pub fn start(app: AppHandle, root: String, query: ContentQuery) -> Result<String, String> {
+    if !Path::new(&root).is_dir() {
+        return Err(format!("{} is not a folder", root));
+    }
+    let regex = build_regex(&query)?;
+
+    let (job_id, control) = app.state::<JobRegistry>().start("search");
+    let id = job_id.clone();
+
+    std::thread::spawn(move || {
+        let mut progress = Progress {
+            app: &app,
+            status: SearchProgress {
+                job_id: job_id.clone(),
+                files_scanned: 0,
+                files_skipped: 0,
+                hits: 0,
+                state: "running".to_string(),
+                truncated: false,
+            },
+            last_emit: Instant::now(),
+        };
+        progress.emit("running");
+
+        if run(Path::new(&root), &query, &regex, &control, &mut progress) {
+            progress.emit("completed");
+        } else {
+            progress.emit("cancelled");
+        }
+
+        app.state::<JobRegistry>().finish(&job_id);
+    });
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hits(text: &str, query: &ContentQuery, stop_after: usize) -> Vec<(usize, String, Vec<String>, Vec<String>)> {
+        let regex = build_regex(query).unwrap();
+        let mut found = Vec::new();
+        search_text(text, &regex, query.context, &mut |n, line, before, after| {
+            found.push((n, line, before, after));
+            found.len() < stop_after
+        });
+        found
+    }
+
+    #[test]
+    fn literal_search_with_context() {
+        let query = ContentQuery {
+            pattern: "total (eur)".to_string(),
+            ..Default::default()
+        };
+        let found = hits("header\nitems: 3\nTOTAL (EUR): 42\r\nfooter", &query, 10);
+        assert_eq!(
+            found,
+            [(3, "TOTAL (EUR): 42".to_string(), vec!["items: 3".to_string()], vec!["footer".to_string()])]
+        );
+    }
+
+    #[test]
+    fn regex_search_stops_when_asked() {
+        let query = ContentQuery {
+            pattern: r"^\d+$".to_string(),
+            regex: true,
+            case_sensitive: true,
+            context: 0,
+            ..Default::default()
+        };
+        let found = hits("1\na\n22\n333", &query, 2);
+        let lines: Vec<usize> = found.iter().map(|h| h.0).collect();
+        assert_eq!(lines, [1, 3]);
+    }
+
+    #[test]
+    fn skips_binary_and_large_files() {
+        let dir = std::env::temp_dir().join(format!("fileforge-grep-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("text.txt"), "hello").unwrap();
+        fs::write(dir.join("image.bin"), b"PNG\0\0hello").unwrap();
+        // Past the sniffed start a NUL doesn't make it binary
+        let mut late_nul = vec![b'a'; BINARY_SNIFF_LEN];
+        late_nul.extend_from_slice(b"\0tail");
+        fs::write(dir.join("late.txt"), &late_nul).unwrap();
+
+        assert_eq!(read_text(&dir.join("text.txt"), 100).as_deref(), Some("hello"));
+        assert_eq!(read_text(&dir.join("text.txt"), 2), None);
+        assert_eq!(read_text(&dir.join("image.bin"), 100), None);
+        let late = read_text(&dir.join("late.txt"), 100_000).unwrap();
+        assert_eq!(late.len(), BINARY_SNIFF_LEN + 5);
+        assert!(late.ends_with("\0tail"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// What a burst of events for one path boils down to once it has gone quiet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    // Created, or renamed in from a name that was never reported
+    Arrived(PathBuf),
+    // `from` was reported before, so whatever tracks it should follow along
+    Renamed { from: PathBuf, to: PathBuf },
+    Removed(PathBuf),
+}
+
+enum PendingKind {
+    Arrived,
+    RenamedFrom(PathBuf),
+    Removed,
+}
+
+struct Pending {
+    kind: PendingKind,
+    last_event: Instant,
+}
+
+// Collects raw notify events per path and hands each path on once no event has
+// touched it for `window`. A file created and deleted within the window never shows up.
+pub struct Debouncer {
+    window: Duration,
+    pending: HashMap<PathBuf, Pending>,
+    // First half of a rename reported as two events (Windows, inotify)
+    rename_from: Option<(PathBuf, Instant)>,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Debouncer {
+            window,
+            pending: HashMap::new(),
+            rename_from: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty() && self.rename_from.is_none()
+    }
+
+    pub fn push(&mut self, event: Event, now: Instant) {
+        match event.kind {
+            EventKind::Create(_) => {
+                for path in event.paths {
+                    self.arrived(path, now);
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                if let Some((unpaired, _)) = self.rename_from.take() {
+                    self.removed(unpaired, now);
+                }
+                self.rename_from = event.paths.into_iter().next().map(|path| (path, now));
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                for to in event.paths {
+                    match self.rename_from.take() {
+                        Some((from, _)) => self.renamed(from, to, now),
+                        None => self.arrived(to, now),
+                    }
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let mut paths = event.paths.into_iter();
+                if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                    self.renamed(from, to, now);
+                }
+            }
+            // Renames that can't be paired up (macOS): whatever still exists is new
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in event.paths {
+                    if path.exists() {
+                        self.arrived(path, now);
+                    } else {
+                        self.removed(path, now);
+                    }
+                }
+            }
+            // Writes don't make a file new, but they do mean it isn't settled yet
+            EventKind::Modify(_) => {
+                for path in &event.paths {
+                    if let Some(pending) = self.pending.get_mut(path) {
+                        pending.last_event = now;
+                    }
+                }
+            }
+            EventKind::Remove(_) => {
+                for path in event.paths {
+                    self.removed(path, now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn arrived(&mut self, path: PathBuf, now: Instant) {
+        let pending = self.pending.entry(path).or_insert(Pending {
+            kind: PendingKind::Arrived,
+            last_event: now,
+        });
+        if matches!(pending.kind, PendingKind::Removed) {
+            pending.kind = PendingKind::Arrived;
+        }
+        pending.last_event = now;
+    }
+
+    fn removed(&mut self, path: PathBuf, now: Instant) {
+        match self.pending.remove(&path).map(|p| p.kind) {
+            // Never reported, so there's nothing to take back
+            Some(PendingKind::Arrived) => {}
+            // The name that was reported is the one to forget
+            Some(PendingKind::RenamedFrom(origin)) => {
+                self.pending.insert(origin, Pending { kind: PendingKind::Removed, last_event: now });
+            }
+            Some(PendingKind::Removed) | None => {
+                self.pending.insert(path, Pending { kind: PendingKind::Removed, last_event: now });
+            }
+        }
+    }
+
+    fn renamed(&mut self, from: PathBuf, to: PathBuf, now: Instant) {
+        let kind = match self.pending.remove(&from).map(|p| p.kind) {
+            Some(PendingKind::Arrived) => PendingKind::Arrived,
+            Some(PendingKind::RenamedFrom(origin)) => PendingKind::RenamedFrom(origin),
+            Some(PendingKind::Removed) | None => PendingKind::RenamedFrom(from),
+        };
+        match self.pending.get_mut(&to) {
+            // inotify repeats a rename it already reported in two halves
+            Some(existing) if matches!(existing.kind, PendingKind::Arrived) => existing.last_event = now,
+            _ => {
+                self.pending.insert(to, Pending { kind, last_event: now });
+            }
+        }
+    }
+
+    // Changes for every path that has been quiet for the whole window, oldest first
+    pub fn drain(&mut self, now: Instant) -> Vec<Change> {
+        if let Some((from, at)) = self.rename_from.take() {
+            // Never paired: the file was moved out of anything being watched
+            if now.duration_since(at) >= self.window {
+                self.removed(from, at);
+            } else {
+                self.rename_from = Some((from, at));
+            }
+        }
+
+        let mut settled: Vec<(Instant, PathBuf)> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now.duration_since(pending.last_event) >= self.window)
+            .map(|(path, pending)| (pending.last_event, path.clone()))
+            .collect();
+        settled.sort();
+
+        settled
+            .into_iter()
+            .filter_map(|(_, path)| {
+                let change = match self.pending.remove(&path)?.kind {
+                    PendingKind::Arrived => Change::Arrived(path),
+                    PendingKind::RenamedFrom(from) => Change::Renamed { from, to: path },
+                    PendingKind::Removed => Change::Removed(path),
+                };
+                Some(change)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+
+    const WINDOW: Duration = Duration::from_millis(500);
+
+    fn event(kind: EventKind, paths: &[&str]) -> Event {
+        paths.iter().fold(Event::new(kind), |event, path| event.add_path(PathBuf::from(path)))
+    }
+
+    fn create(path: &str) -> Event {
+        event(EventKind::Create(CreateKind::File), &[path])
+    }
+
+    fn write(path: &str) -> Event {
+        event(EventKind::Modify(ModifyKind::Data(DataChange::Any)), &[path])
+    }
+
+    fn rename(mode: RenameMode, paths: &[&str]) -> Event {
+        event(EventKind::Modify(ModifyKind::Name(mode)), paths)
+    }
+
+    fn remove(path: &str) -> Event {
+        event(EventKind::Remove(RemoveKind::File), &[path])
+    }
+
+    fn arrived(path: &str) -> Change {
+        Change::Arrived(PathBuf::from(path))
+    }
+
+    // Pushes each event `step_ms` apart and returns the debouncer with the time of the last one
+    fn feed(events: Vec<Event>, step_ms: u64) -> (Debouncer, Instant) {
+        let mut debouncer = Debouncer::new(WINDOW);
+        let mut now = Instant::now();
+        for event in events {
+            now += Duration::from_millis(step_ms);
+            debouncer.push(event, now);
+        }
+        (debouncer, now)
+    }
+
+    #[test]
+    fn chrome_download_becomes_one_arrival() {
+        // inotify reports the final rename three times: From, To and Both
+        let (mut debouncer, last) = feed(
+            vec![
+                create("d/setup.exe.crdownload"),
+                write("d/setup.exe.crdownload"),
+                write("d/setup.exe.crdownload"),
+                rename(RenameMode::From, &["d/setup.exe.crdownload"]),
+                rename(RenameMode::To, &["d/setup.exe"]),
+                rename(RenameMode::Both, &["d/setup.exe.crdownload", "d/setup.exe"]),
+            ],
+            50,
+        );
+
+        assert!(debouncer.drain(last + Duration::from_millis(100)).is_empty());
+        assert_eq!(debouncer.drain(last + WINDOW), [arrived("d/setup.exe")]);
+        assert!(debouncer.is_empty());
+    }
+
+    #[test]
+    fn duplicate_creates_are_coalesced() {
+        // Nested watched folders each report the same file
+        let (mut debouncer, last) = feed(vec![create("d/a.pdf"), create("d/a.pdf"), create("d/b.pdf")], 10);
+        assert_eq!(debouncer.drain(last + WINDOW), [arrived("d/a.pdf"), arrived("d/b.pdf")]);
+    }
+
+    #[test]
+    fn busy_file_waits_until_quiet() {
+        let mut debouncer = Debouncer::new(WINDOW);
+        let start = Instant::now();
+        debouncer.push(create("d/video.mp4"), start);
+        for i in 1..=10 {
+            let now = start + Duration::from_millis(i * 400);
+            debouncer.push(write("d/video.mp4"), now);
+            assert!(debouncer.drain(now).is_empty());
+        }
+        assert_eq!(debouncer.drain(start + Duration::from_millis(4500)), [arrived("d/video.mp4")]);
+    }
+
+    #[test]
+    fn deleted_before_settling_is_dropped() {
+        let (mut debouncer, last) = feed(
+            vec![
+                create("d/cancelled.zip.crdownload"),
+                write("d/cancelled.zip.crdownload"),
+                remove("d/cancelled.zip.crdownload"),
+            ],
+            50,
+        );
+        assert!(debouncer.drain(last + WINDOW).is_empty());
+        assert!(debouncer.is_empty());
+    }
+
+    #[test]
+    fn renames_of_reported_files_are_followed() {
+        let mut debouncer = Debouncer::new(WINDOW);
+        let start = Instant::now();
+        debouncer.push(create("d/movie.mkv.part"), start);
+        assert_eq!(debouncer.drain(start + WINDOW), [arrived("d/movie.mkv.part")]);
+
+        // Already handed on under its temporary name, so the rename is passed along
+        let later = start + Duration::from_secs(5);
+        debouncer.push(rename(RenameMode::Both, &["d/movie.mkv.part", "d/movie.mkv"]), later);
+        assert_eq!(
+            debouncer.drain(later + WINDOW),
+            [Change::Renamed {
+                from: PathBuf::from("d/movie.mkv.part"),
+                to: PathBuf::from("d/movie.mkv"),
+            }]
+        );
+
+        // ...and so is its removal, under the name that was reported
+        let end = later + Duration::from_secs(5);
+        debouncer.push(remove("d/movie.mkv"), end);
+        assert_eq!(debouncer.drain(end + WINDOW), [Change::Removed(PathBuf::from("d/movie.mkv"))]);
+    }
+
+    #[test]
+    fn unpaired_rename_counts_as_removal() {
+        let mut debouncer = Debouncer::new(WINDOW);
+        let start = Instant::now();
+        debouncer.push(rename(RenameMode::From, &["d/report.pdf"]), start);
+        assert!(debouncer.drain(start + WINDOW / 2).is_empty());
+        assert_eq!(debouncer.drain(start + WINDOW), [Change::Removed(PathBuf::from("d/report.pdf"))]);
+        assert!(debouncer.is_empty());
+    }
+}
@@ -0,0 +1,352 @@
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::jobs::{JobControl, JobRegistry};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// Per folder, only the biggest files are kept by name - the rest are just counted
+const FILES_KEPT_PER_DIR: usize = 32;
+const DEFAULT_DEPTH: usize = 2;
+const DEFAULT_MAX_CHILDREN: usize = 50;
+
+// Sizes are apparent sizes (file length), symlinks aren't followed and hard links
+// are counted once per name
+struct DirNode {
+    name: Box<str>,
+    // Everything below this folder
+    bytes: u64,
+    files: u64,
+    dirs: Vec<DirNode>,
+    // Biggest direct children that are files, largest first
+    largest_files: Vec<(Box<str>, u64)>,
+    direct_file_count: u64,
+    direct_file_bytes: u64,
+}
+
+struct ScanResult {
+    tree: DirNode,
+    scanned_at: String,
+}
+
+// Finished scans by root, so drilling into a folder doesn't rescan it
+#[derive(Default)]
+pub struct DiskUsageCache {
+    scans: Mutex<HashMap<PathBuf, Arc<ScanResult>>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    files: AtomicU64,
+    bytes: AtomicU64,
+    // Folders that couldn't be read
+    errors: AtomicU64,
+}
+
+#[derive(Serialize, Clone)]
+struct ScanProgress {
+    job_id: String,
+    root: String,
+    files: u64,
+    bytes: u64,
+    errors: u64,
+    state: String, // "running", "paused", "completed", "cancelled"
+}
+
+#[derive(Serialize)]
+pub struct UsageNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub bytes: u64,
+    pub files: u64,
+    // Largest first; empty past the requested depth
+    pub children: Vec<UsageNode>,
+    // What didn't make the max_children cut (or wasn't kept by name), summed up
+    pub other_bytes: u64,
+    pub other_count: u64,
+}
+
+#[derive(Serialize)]
+pub struct DiskUsage {
+    pub root: String,
+    pub scanned_at: String,
+    pub tree: UsageNode,
+}
+
+// Returns None if the job was cancelled part way
+fn scan_dir(path: &Path, name: &str, control: &JobControl, counters: &Counters) -> Option<DirNode> {
+    if !control.checkpoint() {
+        return None;
+    }
+
+    let mut node = DirNode {
+        name: name.into(),
+        bytes: 0,
+        files: 0,
+        dirs: Vec::new(),
+        largest_files: Vec::new(),
+        direct_file_count: 0,
+        direct_file_bytes: 0,
+    };
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => {
+            counters.errors.fetch_add(1, Ordering::Relaxed);
+            return Some(node);
+        }
+    };
+
+    let mut subdirs = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            subdirs.push((entry.path(), entry.file_name().to_string_lossy().to_string()));
+            continue;
+        }
+        let size = if file_type.is_file() {
+            entry.metadata().map(|m| m.len()).unwrap_or(0)
+        } else {
+            0
+        };
+        node.direct_file_count += 1;
+        node.direct_file_bytes += size;
+        node.largest_files.push((entry.file_name().to_string_lossy().into(), size));
+        counters.files.fetch_add(1, Ordering::Relaxed);
+        counters.bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    node.largest_files.sort_unstable_by_key(|f| std::cmp::Reverse(f.1));
+    node.largest_files.truncate(FILES_KEPT_PER_DIR);
+
+    let dirs: Option<Vec<DirNode>> = subdirs
+        .par_iter()
+        .map(|(path, name)| scan_dir(path, name, control, counters))
+        .collect();
+    node.dirs = dirs?;
+
+    node.bytes = node.direct_file_bytes + node.dirs.iter().map(|d| d.bytes).sum::<u64>();
+    node.files = node.direct_file_count + node.dirs.iter().map(|d| d.files).sum::<u64>();
+    Some(node)
+}
+
+fn view(node: &DirNode, path: &Path, depth: usize, max_children: usize) -> UsageNode {
+    let mut usage = UsageNode {
+        name: node.name.to_string(),
+        path: path.to_string_lossy().to_string(),
+        is_dir: true,
+        bytes: node.bytes,
+        files: node.files,
+        children: Vec::new(),
+        other_bytes: 0,
+        other_count: 0,
+    };
+    if depth == 0 {
+        return usage;
+    }
+
+    let mut children: Vec<UsageNode> = node
+        .dirs
+        .iter()
+        .map(|dir| view(dir, &path.join(&*dir.name), depth - 1, max_children))
+        .collect();
+    children.extend(node.largest_files.iter().map(|(name, bytes)| UsageNode {
+        name: name.to_string(),
+        path: path.join(&**name).to_string_lossy().to_string(),
+        is_dir: false,
+        bytes: *bytes,
+        files: 1,
+        children: Vec::new(),
+        other_bytes: 0,
+        other_count: 0,
+    }));
+    children.sort_by_key(|c| std::cmp::Reverse(c.bytes));
+
+    // Files too small to be kept by name still count towards "other"
+    let kept_file_bytes: u64 = node.largest_files.iter().map(|f| f.1).sum();
+    usage.other_bytes = node.direct_file_bytes - kept_file_bytes;
+    usage.other_count = node.direct_file_count - node.largest_files.len() as u64;
+
+    for dropped in children.drain(max_children.min(children.len())..) {
+        usage.other_bytes += dropped.bytes;
+        usage.other_count += 1;
+    }
+    usage.children = children;
+    usage
+}
+
+fn find_node<'a>(tree: &'a DirNode, relative: &Path) -> Option<&'a DirNode> {
+    relative.components().try_fold(tree, |node, component| {
+        let name = component.as_os_str().to_string_lossy();
+        node.dirs.iter().find(|d| *d.name == *name)
+    })
+}
+
+impl DiskUsageCache {
+    // Drills into any folder under a cached root. Errors if that root hasn't been scanned.
+    pub fn get(&self, path: &Path, depth: Option<usize>, max_children: Option<usize>) -> Result<DiskUsage, String> {
+        let scans = self.scans.lock().unwrap_or_else(|e| e.into_inner());
+        let (root, scan) = scans
+            .iter()
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .ok_or_else(|| format!("{} hasn't been scanned yet", path.display()))?;
+
+        let relative = path.strip_prefix(root).map_err(|e| e.to_string())?;
+        let node = find_node(&scan.tree, relative).ok_or_else(|| format!("{} is not a folder in the last scan", path.display()))?;
+
+        Ok(DiskUsage {
+            root: root.to_string_lossy().to_string(),
+            scanned_at: scan.scanned_at.clone(),
+            tree: view(node, path, depth.unwrap_or(DEFAULT_DEPTH), max_children.unwrap_or(DEFAULT_MAX_CHILDREN)),
+        })
+    }
+
+    fn insert(&self, root: PathBuf, result: ScanResult) {
+        let mut scans = self.scans.lock().unwrap_or_else(|e| e.into_inner());
+        // A rescan of a parent supersedes scans of its subfolders
+        scans.retain(|existing, _| !existing.starts_with(&root));
+        scans.insert(root, Arc::new(result));
+    }
+}
+
+// Starts a background scan and returns its job id right away. Progress arrives as
+// "disk-usage-progress" events; once completed, get_disk_usage serves the results.
+pub fn start(app: AppHandle, root: String) -> Result<String, String> {
+    let root_path = PathBuf::from(&root);
+    if !root_path.is_dir() {
+        return Err(format!("{} is not a folder", root));
+    }
+
+    let pool = crate::jobs::thread_pool("disk-usage")?;
+    let (job_id, control) = app.state::<JobRegistry>().start("disk-usage");
+    let id = job_id.clone();
+
+    std::thread::spawn(move || {
+        let counters = Counters::default();
+        let done = AtomicBool::new(false);
+        let emit = |state: &str| {
+            let _ = app.emit(
+                "disk-usage-progress",
+                ScanProgress {
+                    job_id: job_id.clone(),
+                    root: root.clone(),
+                    files: counters.files.load(Ordering::Relaxed),
+                    bytes: counters.bytes.load(Ordering::Relaxed),
+                    errors: counters.errors.load(Ordering::Relaxed),
+                    state: state.to_string(),
+                },
+            );
+        };
+
+        let tree = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !done.load(Ordering::SeqCst) {
+                    emit(if control.is_paused() { "paused" } else { "running" });
+                    std::thread::sleep(PROGRESS_INTERVAL);
+                }
+            });
+            let tree = pool.install(|| scan_dir(&root_path, &root, &control, &counters));
+            done.store(true, Ordering::SeqCst);
+            tree
+        });
+
+        match tree {
+            Some(tree) => {
+                app.state::<DiskUsageCache>().insert(
+                    root_path.clone(),
+                    ScanResult {
+                        tree,
+                        scanned_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                    },
+                );
+                emit("completed");
+            }
+            None => emit("cancelled"),
+        }
+
+        app.state::<JobRegistry>().finish(&job_id);
+    });
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_and_views_tree() {
+        let root = std::env::temp_dir().join(format!("fileforge-usage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("videos/2026")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("videos/2026/clip.mp4"), vec![0u8; 5000]).unwrap();
+        fs::write(root.join("videos/trailer.mp4"), vec![0u8; 3000]).unwrap();
+        fs::write(root.join("docs/a.txt"), vec![0u8; 10]).unwrap();
+        fs::write(root.join("docs/b.txt"), vec![0u8; 20]).unwrap();
+        fs::write(root.join("readme.md"), vec![0u8; 100]).unwrap();
+
+        let control = JobControl::default();
+        let counters = Counters::default();
+        let tree = scan_dir(&root, "root", &control, &counters).unwrap();
+        assert_eq!(tree.bytes, 8130);
+        assert_eq!(tree.files, 5);
+        assert_eq!(counters.files.load(Ordering::Relaxed), 5);
+
+        let usage = view(&tree, &root, 1, 2);
+        let names: Vec<&str> = usage.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["videos", "readme.md"]);
+        assert!(usage.children[0].children.is_empty());
+        assert_eq!((usage.other_bytes, usage.other_count), (30, 1));
+
+        let docs = find_node(&tree, Path::new("docs")).unwrap();
+        assert_eq!(view(docs, &root.join("docs"), 1, 10).children[0].name, "b.txt");
+        assert!(find_node(&tree, Path::new("videos/2026/clip.mp4")).is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn paused_scan_leaves_other_rayon_work_alone() {
+        let root = std::env::temp_dir().join(format!("fileforge-usage-paused-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for n in 0..500 {
+            fs::create_dir_all(root.join(format!("dir{}/sub", n))).unwrap();
+            fs::write(root.join(format!("dir{}/sub/file", n)), "x").unwrap();
+        }
+
+        let registry = JobRegistry::default();
+        let (id, control) = registry.start("disk-usage");
+        let pool = crate::jobs::thread_pool("disk-usage").unwrap();
+        let counters = Counters::default();
+
+        let (sum, files) = std::thread::scope(|scope| {
+            let scan = scope.spawn(|| pool.install(|| scan_dir(&root, "root", &control, &counters)));
+            // Paused once the scan is into the subfolders, so its workers wait in there
+            while counters.files.load(Ordering::Relaxed) == 0 && !scan.is_finished() {
+                std::thread::yield_now();
+            }
+            registry.set_paused(&id, true).unwrap();
+
+            // Work on the global pool still gets done while the scan waits
+            let (sender, receiver) = std::sync::mpsc::channel();
+            std::thread::spawn(move || sender.send((0..10_000u64).into_par_iter().sum::<u64>()));
+            let sum = receiver.recv_timeout(Duration::from_secs(10));
+
+            registry.set_paused(&id, false).unwrap();
+            (sum, scan.join().unwrap().map(|tree| tree.files))
+        });
+        assert_eq!(sum.ok(), Some(49_995_000));
+        assert_eq!(files, Some(500));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
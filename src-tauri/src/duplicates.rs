@@ -0,0 +1,453 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use walkdir::WalkDir;
+
+use crate::jobs::{JobControl, JobRegistry};
+use crate::journal::Operation;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// The partial hash reads this much from the start of each file
+const PARTIAL_HASH_LEN: u64 = 64 * 1024;
+const READ_BUFFER: usize = 256 * 1024;
+
+#[derive(Serialize, Clone)]
+pub struct DuplicateSet {
+    pub size: u64,
+    // SHA-256, hex
+    pub hash: String,
+    pub paths: Vec<String>,
+    // Freed by keeping a single copy
+    pub reclaimable: u64,
+}
+
+#[derive(Serialize, Clone)]
+struct DuplicatesProgress {
+    job_id: String,
+    stage: String, // "scanning", "partial_hash", "full_hash"
+    files_scanned: u64,
+    files_hashed: u64,
+    state: String, // "running", "paused", "completed", "cancelled"
+    // Filled in on completion, biggest savings first
+    sets: Vec<DuplicateSet>,
+    total_reclaimable: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolveMode {
+    // Extra copies go to the trash, through the same path as delete_file
+    #[default]
+    Trash,
+    // Extra copies become hardlinks to the kept file (same volume only)
+    Hardlink,
+}
+
+#[derive(Serialize, Default)]
+pub struct ResolveSummary {
+    pub resolved: usize,
+    pub freed_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+fn hash_reader(reader: &mut dyn Read, control: Option<&JobControl>) -> Result<Option<String>, String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; READ_BUFFER];
+    loop {
+        if control.is_some_and(|c| !c.checkpoint()) {
+            return Ok(None);
+        }
+        let read = reader.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+// Full SHA-256 of a file, hex encoded
+pub fn hash_file(path: &Path) -> Result<String, String> {
+    let mut file = File::open(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    hash_reader(&mut file, None).map(|hash| hash.unwrap_or_default())
+}
+
+// Hashes the first `len` bytes, or the whole file. Ok(None) if cancelled.
+fn hash_prefix(path: &Path, len: Option<u64>, control: &JobControl) -> Result<Option<String>, String> {
+    let mut file = File::open(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    match len {
+        Some(len) => hash_reader(&mut (&mut file).take(len), Some(control)),
+        None => hash_reader(&mut file, Some(control)),
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    stage: Mutex<&'static str>,
+    scanned: AtomicU64,
+    hashed: AtomicU64,
+}
+
+impl Counters {
+    fn set_stage(&self, stage: &'static str) {
+        *self.stage.lock().unwrap_or_else(|e| e.into_inner()) = stage;
+    }
+}
+
+// Two names for the same file on disk. Hardlinked copies take no extra space.
+#[cfg(unix)]
+fn file_identity(_path: &Path, metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// std doesn't expose the volume serial and file index on stable, so they come from the handle
+#[cfg(windows)]
+fn file_identity(path: &Path, _metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION};
+
+    // No read access needed just to ask about the file
+    let file = fs::OpenOptions::new().access_mode(0).open(path).ok()?;
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+        return None;
+    }
+    let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+    Some((u64::from(info.dwVolumeSerialNumber), index))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn file_identity(_path: &Path, _metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+// Regular files of at least min_size, grouped by size. Overlapping roots are fine.
+fn collect_by_size(roots: &[PathBuf], min_size: u64, control: &JobControl, counters: &Counters) -> Option<HashMap<u64, Vec<PathBuf>>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    let mut seen_paths = HashSet::new();
+    let mut seen_files = HashSet::new();
+
+    for root in roots {
+        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if !control.checkpoint() {
+                return None;
+            }
+            if !entry.file_type().is_file() || !seen_paths.insert(entry.path().to_path_buf()) {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else { continue };
+            if metadata.len() < min_size {
+                continue;
+            }
+            if let Some(identity) = file_identity(entry.path(), &metadata) {
+                if !seen_files.insert(identity) {
+                    continue;
+                }
+            }
+            counters.scanned.fetch_add(1, Ordering::Relaxed);
+            by_size.entry(metadata.len()).or_default().push(entry.into_path());
+        }
+    }
+    Some(by_size)
+}
+
+// (size, hash, paths) - always at least two paths
+type HashGroup = (u64, String, Vec<PathBuf>);
+
+// Splits every group by a hash, keeping only the buckets that still have company.
+// Unreadable files drop out. None if cancelled.
+fn refine(groups: Vec<(u64, Vec<PathBuf>)>, hash_len: Option<u64>, control: &JobControl, counters: &Counters) -> Option<Vec<HashGroup>> {
+    let hashed_groups: Vec<Option<Vec<HashGroup>>> = groups
+        .into_par_iter()
+        .map(|(size, paths)| {
+            let mut buckets: HashMap<String, Vec<PathBuf>> = HashMap::new();
+            for path in paths {
+                match hash_prefix(&path, hash_len, control) {
+                    Ok(Some(hash)) => buckets.entry(hash).or_default().push(path),
+                    Ok(None) => return None,
+                    Err(e) => eprintln!("Skipping {}", e),
+                }
+                counters.hashed.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(
+                buckets
+                    .into_iter()
+                    .filter(|(_, paths)| paths.len() > 1)
+                    .map(|(hash, paths)| (size, hash, paths))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    hashed_groups.into_iter().try_fold(Vec::new(), |mut all, group| {
+        all.extend(group?);
+        Some(all)
+    })
+}
+
+fn find(roots: &[PathBuf], min_size: u64, control: &JobControl, counters: &Counters) -> Option<Vec<DuplicateSet>> {
+    counters.set_stage("scanning");
+    let by_size = collect_by_size(roots, min_size, control, counters)?;
+    let candidates: Vec<(u64, Vec<PathBuf>)> = by_size.into_iter().filter(|(_, paths)| paths.len() > 1).collect();
+
+    counters.set_stage("partial_hash");
+    let partial = refine(candidates, Some(PARTIAL_HASH_LEN), control, counters)?;
+
+    // Files no bigger than the partial read were hashed in full already
+    let (complete, needs_full): (Vec<_>, Vec<_>) = partial.into_iter().partition(|(size, _, _)| *size <= PARTIAL_HASH_LEN);
+    counters.set_stage("full_hash");
+    let full = refine(
+        needs_full.into_iter().map(|(size, _, paths)| (size, paths)).collect(),
+        None,
+        control,
+        counters,
+    )?;
+
+    let mut sets: Vec<DuplicateSet> = complete
+        .into_iter()
+        .chain(full)
+        .map(|(size, hash, paths)| {
+            let mut paths: Vec<String> = paths.into_iter().map(|p| p.to_string_lossy().to_string()).collect();
+            paths.sort();
+            DuplicateSet {
+                reclaimable: size * (paths.len() as u64 - 1),
+                size,
+                hash,
+                paths,
+            }
+        })
+        .collect();
+    sets.sort_by(|a, b| b.reclaimable.cmp(&a.reclaimable).then_with(|| a.paths.cmp(&b.paths)));
+    Some(sets)
+}
+
+// Starts a background search over `roots` and returns its job id right away. Progress and,
+// at the end, the duplicate sets arrive as "duplicates-progress" events.
+pub fn start(app: AppHandle, roots: Vec<String>, min_size: u64) -> Result<String, String> {
+    let roots: Vec<PathBuf> = roots.into_iter().map(PathBuf::from).collect();
+    if let Some(bad) = roots.iter().find(|r| !r.is_dir()) {
+        return Err(format!("{} is not a folder", bad.display()));
+    }
+
+    let pool = crate::jobs::thread_pool("duplicates")?;
+    let (job_id, control) = app.state::<JobRegistry>().start("duplicates");
+    let id = job_id.clone();
+
+    std::thread::spawn(move || {
+        let counters = Counters::default();
+        let report = |state: &str, sets: Vec<DuplicateSet>| {
+            let _ = app.emit(
+                "duplicates-progress",
+                DuplicatesProgress {
+                    job_id: job_id.clone(),
+                    stage: counters.stage.lock().unwrap_or_else(|e| e.into_inner()).to_string(),
+                    files_scanned: counters.scanned.load(Ordering::Relaxed),
+                    files_hashed: counters.hashed.load(Ordering::Relaxed),
+                    state: state.to_string(),
+                    total_reclaimable: sets.iter().map(|s| s.reclaimable).sum(),
+                    sets,
+                },
+            );
+        };
+
+        let done = AtomicBool::new(false);
+        let result = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !done.load(Ordering::SeqCst) {
+                    report(if control.is_paused() { "paused" } else { "running" }, Vec::new());
+                    std::thread::sleep(PROGRESS_INTERVAL);
+                }
+            });
+            let result = pool.install(|| find(&roots, min_size.max(1), &control, &counters));
+            done.store(true, Ordering::SeqCst);
+            result
+        });
+
+        match result {
+            Some(sets) => {
+                println!("Found {} duplicate sets", sets.len());
+                report("completed", sets);
+            }
+            None => report("cancelled", Vec::new()),
+        }
+        app.state::<JobRegistry>().finish(&job_id);
+    });
+
+    Ok(id)
+}
+
+fn temp_beside(path: &Path, suffix: &str) -> PathBuf {
+    path.with_file_name(format!(
+        ".{}.{}",
+        path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        suffix
+    ))
+}
+
+// Replaces `duplicate` with a hardlink to `keep`. The link is made under a temporary
+// name first, so the duplicate is only replaced once the link exists.
+pub fn replace_with_hardlink(keep: &Path, duplicate: &Path) -> Result<(), String> {
+    let temp = temp_beside(duplicate, "fileforge-link");
+    fs::hard_link(keep, &temp).map_err(|e| format!("Cannot link {}: {}", duplicate.display(), e))?;
+    fs::rename(&temp, duplicate).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("Cannot replace {}: {}", duplicate.display(), e)
+    })
+}
+
+// Undoes replace_with_hardlink by giving `duplicate` its own copy of `keep` again.
+// The content is the same; the times and permissions are the kept file's.
+pub fn break_hardlink(keep: &Path, duplicate: &Path) -> Result<(), String> {
+    if fs::symlink_metadata(duplicate).is_err() {
+        return Err(format!("{} no longer exists", duplicate.display()));
+    }
+    let temp = temp_beside(duplicate, "fileforge-copy");
+    if let Err(e) = crate::transfer::copy_file_preserving(keep, &temp) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    fs::rename(&temp, duplicate).map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("Cannot replace {}: {}", duplicate.display(), e)
+    })
+}
+
+// Every duplicate is re-hashed against `keep` first, in case it changed since the scan.
+// Trashed copies are journaled by delete_file; each new link is passed to `record_link`,
+// which outside of tests journals it under the same batch.
+pub fn resolve(keep: &str, duplicates: &[String], mode: ResolveMode, batch_id: Option<String>, record_link: &dyn Fn(Operation)) -> Result<ResolveSummary, String> {
+    let keep_path = Path::new(keep);
+    let keep_hash = hash_file(keep_path)?;
+    let keep_identity = fs::metadata(keep_path).ok().and_then(|m| file_identity(keep_path, &m));
+    let mut summary = ResolveSummary::default();
+
+    for duplicate in duplicates {
+        let path = Path::new(duplicate);
+        if path == keep_path {
+            continue;
+        }
+        let result = fs::metadata(path)
+            .map_err(|e| format!("Cannot read {}: {}", duplicate, e))
+            .and_then(|metadata| {
+                // Already a hardlink to `keep`: nothing to free, and renaming a link over
+                // another name for the same file is a no-op that would leave the temp link behind
+                if keep_identity.is_some() && file_identity(path, &metadata) == keep_identity {
+                    return Ok(None);
+                }
+                if hash_file(path)? != keep_hash {
+                    return Err(format!("{} no longer matches {}", duplicate, keep));
+                }
+                match mode {
+                    ResolveMode::Trash => {
+                        let trash_id = crate::delete_file(duplicate.clone(), batch_id.clone())?;
+                        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                        crate::record_history(name, duplicate.clone(), metadata.len(), "deleted".to_string(), None, None, trash_id)?;
+                    }
+                    ResolveMode::Hardlink => {
+                        replace_with_hardlink(keep_path, path)?;
+                        record_link(Operation::Hardlink {
+                            path: duplicate.clone(),
+                            target: keep.to_string(),
+                        });
+                    }
+                }
+                Ok(Some(metadata.len()))
+            });
+
+        match result {
+            Ok(Some(freed)) => {
+                summary.resolved += 1;
+                summary.freed_bytes += freed;
+            }
+            Ok(None) => {}
+            Err(e) => summary.errors.push(e),
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_duplicate_sets() {
+        let root = std::env::temp_dir().join(format!("fileforge-dupes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("nested")).unwrap();
+
+        let big: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let mut big_variant = big.clone();
+        *big_variant.last_mut().unwrap() ^= 1;
+
+        fs::write(root.join("report.pdf"), &big).unwrap();
+        fs::write(root.join("report (1).pdf"), &big).unwrap();
+        fs::write(root.join("nested/report.pdf"), &big).unwrap();
+        // Same size and same first 64 KiB - only the full hash tells it apart
+        fs::write(root.join("nearly.pdf"), &big_variant).unwrap();
+        fs::write(root.join("a.txt"), b"same").unwrap();
+        fs::write(root.join("b.txt"), b"same").unwrap();
+        fs::write(root.join("c.txt"), b"diff").unwrap();
+
+        let control = JobControl::default();
+        let sets = find(&[root.clone(), root.join("nested")], 1, &control, &Counters::default()).unwrap();
+
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].paths.len(), 3);
+        assert_eq!(sets[0].reclaimable, 400_000);
+        assert_eq!(sets[0].hash, hash_file(&root.join("report.pdf")).unwrap());
+        assert_eq!(sets[1].paths.len(), 2);
+        assert!(sets[1].paths[0].ends_with("a.txt"));
+
+        replace_with_hardlink(&root.join("a.txt"), &root.join("b.txt")).unwrap();
+        let again = find(std::slice::from_ref(&root), 1, &control, &Counters::default()).unwrap();
+        // The hardlinked pair is one file now
+        assert_eq!(again.len(), 1);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(any(unix, windows))]
+    #[test]
+    fn existing_hardlinks_are_left_alone() {
+        let root = std::env::temp_dir().join(format!("fileforge-dupes-links-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let (keep, linked, copy) = (root.join("keep.bin"), root.join("linked.bin"), root.join("copy.bin"));
+        fs::write(&keep, b"same bytes").unwrap();
+        fs::hard_link(&keep, &linked).unwrap();
+        fs::write(&copy, b"same bytes").unwrap();
+
+        let duplicates = [linked.to_string_lossy().to_string(), copy.to_string_lossy().to_string()];
+        let linked_now = std::cell::RefCell::new(Vec::new());
+        let record = |operation: Operation| linked_now.borrow_mut().push(operation);
+        let summary = resolve(&keep.to_string_lossy(), &duplicates, ResolveMode::Hardlink, None, &record).unwrap();
+        assert_eq!((summary.resolved, summary.freed_bytes), (1, 10));
+        assert!(summary.errors.is_empty());
+        assert!(matches!(&linked_now.borrow()[..], [Operation::Hardlink { path, .. }] if path == &duplicates[1]));
+
+        let identity = |path: &Path| file_identity(path, &fs::metadata(path).unwrap());
+        assert_eq!(identity(&copy), identity(&keep));
+        let mut names: Vec<String> = fs::read_dir(&root).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        assert_eq!(names, ["copy.bin", "keep.bin", "linked.bin"]);
+
+        // Undo gives the copy back its own file
+        break_hardlink(&keep, &copy).unwrap();
+        assert_ne!(identity(&copy), identity(&keep));
+        assert_eq!(fs::read(&copy).unwrap(), b"same bytes");
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 3);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
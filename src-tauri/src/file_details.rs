@@ -0,0 +1,96 @@
+use serde::Serialize;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The optional extras for a listing entry. Times are seconds since the Unix epoch,
+// and None wherever the platform or filesystem doesn't record them.
+#[derive(Serialize, Clone)]
+pub struct FileDetails {
+    // "file", "dir", "symlink" or "other" (sockets, devices, ...)
+    pub kind: String,
+    pub created: Option<u64>,
+    pub modified: Option<u64>,
+    pub accessed: Option<u64>,
+    pub readonly: bool,
+    pub hidden: bool,
+    pub symlink_target: Option<String>,
+    // Unix only
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    // Sniffed from the first bytes, so plain text and unknown formats have none
+    pub mime: Option<String>,
+}
+
+fn unix_secs(time: std::io::Result<SystemTime>) -> Option<u64> {
+    time.ok()?.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+#[cfg(windows)]
+fn has_hidden_attribute(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn has_hidden_attribute(_metadata: &Metadata) -> bool {
+    false
+}
+
+fn is_hidden(path: &Path, metadata: &Metadata) -> bool {
+    path.file_name().is_some_and(|n| n.to_string_lossy().starts_with('.')) || has_hidden_attribute(metadata)
+}
+
+// `metadata` is the entry's own (not followed) metadata, as read_dir hands it out.
+// Symlinks cost one extra call to read the link; only regular files get sniffed.
+pub fn gather(path: &Path, metadata: &Metadata) -> FileDetails {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "dir"
+    } else if file_type.is_file() {
+        "file"
+    } else {
+        "other"
+    };
+
+    let symlink_target = if file_type.is_symlink() {
+        fs::read_link(path).ok().map(|t| t.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    #[cfg(unix)]
+    let (mode, uid, gid) = {
+        use std::os::unix::fs::MetadataExt;
+        (Some(metadata.mode()), Some(metadata.uid()), Some(metadata.gid()))
+    };
+    #[cfg(not(unix))]
+    let (mode, uid, gid) = (None, None, None);
+
+    let mime = if file_type.is_file() {
+        infer::get_from_path(path)
+            .ok()
+            .flatten()
+            .map(|t| t.mime_type().to_string())
+    } else {
+        None
+    };
+
+    FileDetails {
+        kind: kind.to_string(),
+        created: unix_secs(metadata.created()),
+        modified: unix_secs(metadata.modified()),
+        accessed: unix_secs(metadata.accessed()),
+        readonly: metadata.permissions().readonly(),
+        hidden: is_hidden(path, metadata),
+        symlink_target,
+        mode,
+        uid,
+        gid,
+        mime,
+    }
+}
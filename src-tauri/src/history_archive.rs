@@ -0,0 +1,203 @@
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::DownloadHistoryEntry;
+
+// Where entries without a usable timestamp are archived
+const UNKNOWN_MONTH: &str = "unknown";
+
+fn archive_dir() -> PathBuf {
+    crate::get_app_dir().join("history-archive")
+}
+
+// The "YYYY-MM" an entry is archived under. Timestamps can come from an imported file,
+// so anything that doesn't start with a real month goes to the unknown bucket.
+fn month_of(timestamp: &str) -> String {
+    match timestamp.get(..7) {
+        Some(month) if is_month(month) => month.to_string(),
+        _ => UNKNOWN_MONTH.to_string(),
+    }
+}
+
+// Appends entries to history-archive/YYYY-MM.jsonl.gz by the month they happened in.
+// Each call adds a new gzip member, which MultiGzDecoder reads back as one stream.
+pub fn archive(entries: &[DownloadHistoryEntry]) -> Result<(), String> {
+    archive_in(&archive_dir(), entries)
+}
+
+fn archive_in(dir: &Path, entries: &[DownloadHistoryEntry]) -> Result<(), String> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create history archive: {}", e))?;
+
+    let mut by_month: BTreeMap<String, Vec<&DownloadHistoryEntry>> = BTreeMap::new();
+    for entry in entries {
+        by_month.entry(month_of(&entry.timestamp)).or_default().push(entry);
+    }
+
+    for (month, month_entries) in by_month {
+        let path = dir.join(format!("{}.jsonl.gz", month));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        for entry in month_entries {
+            let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+            writeln!(encoder, "{}", line).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        }
+        let file = encoder
+            .finish()
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Archived months, newest first ("2026-01", ...)
+pub fn list_months() -> Vec<String> {
+    let mut months: Vec<String> = fs::read_dir(archive_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    e.file_name()
+                        .to_string_lossy()
+                        .strip_suffix(".jsonl.gz")
+                        .map(str::to_string)
+                })
+                // Stray files would make every unfiltered query fail in read_month
+                .filter(|month| is_month(month))
+                .collect()
+        })
+        .unwrap_or_default();
+    months.sort_by(|a, b| b.cmp(a));
+    months
+}
+
+// "YYYY-MM" (or the unknown bucket) - anything else could point outside the archive folder
+fn is_month(month: &str) -> bool {
+    month == UNKNOWN_MONTH
+        || (month.len() == 7 && chrono::NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").is_ok())
+}
+
+fn read_month(month: &str) -> Result<Vec<DownloadHistoryEntry>, String> {
+    if !is_month(month) {
+        return Err(format!("Invalid archive month: {}", month));
+    }
+    read_file(&archive_dir().join(format!("{}.jsonl.gz", month)))
+}
+
+// Rows are archived before the delete that rolls them off commits, so if that fails they
+// get archived again next time. Repeats of (timestamp, original_path) are those copies.
+fn read_file(path: &Path) -> Result<Vec<DownloadHistoryEntry>, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    for line in BufReader::new(MultiGzDecoder::new(file)).lines() {
+        let line = line.map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A damaged line shouldn't hide the rest of the month
+        match serde_json::from_str::<DownloadHistoryEntry>(&line) {
+            Ok(entry) => {
+                if seen.insert((entry.timestamp.clone(), entry.original_path.clone())) {
+                    entries.push(entry);
+                }
+            }
+            Err(e) => eprintln!("Skipping bad archive line in {}: {}", path.display(), e),
+        }
+    }
+    Ok(entries)
+}
+
+// All filters are optional; search matches name or original path, case-insensitive
+pub fn query(month: Option<&str>, action: Option<&str>, search: Option<&str>) -> Result<Vec<DownloadHistoryEntry>, String> {
+    let months = match month {
+        Some(month) => vec![month.to_string()],
+        None => list_months(),
+    };
+    let needle = search.map(str::to_lowercase);
+
+    let mut results = Vec::new();
+    for month in months {
+        for entry in read_month(&month)? {
+            if action.is_some_and(|a| entry.action != a) {
+                continue;
+            }
+            if let Some(needle) = &needle {
+                if !entry.name.to_lowercase().contains(needle) && !entry.original_path.to_lowercase().contains(needle) {
+                    continue;
+                }
+            }
+            results.push(entry);
+        }
+    }
+
+    results.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_months_name_archive_files() {
+        for month in ["2026-01", "1999-12", UNKNOWN_MONTH] {
+            assert!(is_month(month), "{}", month);
+        }
+        for month in ["2026-13", "2026-1", "26-01-01", "../../x", "2026-01/../../secrets", "", "..\\2026-01"] {
+            assert!(!is_month(month), "{}", month);
+        }
+        assert!(query(Some("../../x"), None, None).is_err_and(|e| e.starts_with("Invalid archive month")));
+    }
+
+    #[test]
+    fn bad_timestamps_are_archived_as_unknown() {
+        assert_eq!(month_of("2026-03-14 09:26:53"), "2026-03");
+        for timestamp in ["../../x", "../../x.jsonl", "2026-13-01 00:00:00", "yesterday", "", "2026-0"] {
+            assert_eq!(month_of(timestamp), UNKNOWN_MONTH, "{}", timestamp);
+        }
+    }
+
+    #[test]
+    fn rows_archived_twice_read_back_once() {
+        let dir = std::env::temp_dir().join(format!("fileforge-archive-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let entry = |name: &str, timestamp: &str| DownloadHistoryEntry {
+            name: name.to_string(),
+            original_path: format!("/downloads/{}", name),
+            size: 1,
+            timestamp: timestamp.to_string(),
+            action: "moved".to_string(),
+            destination: None,
+            trash_id: None,
+            detected_at: None,
+            content_hash: None,
+        };
+        let rolled_off = [entry("a.zip", "2026-03-01 10:00:00"), entry("b.zip", "2026-03-01 10:00:00")];
+
+        // As if the delete after the first archive had failed to commit
+        archive_in(&dir, &rolled_off).unwrap();
+        archive_in(&dir, &rolled_off).unwrap();
+        archive_in(&dir, &[entry("a.zip", "2026-03-02 09:00:00")]).unwrap();
+
+        let read = read_file(&dir.join("2026-03.jsonl.gz")).unwrap();
+        let names: Vec<&str> = read.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["a.zip", "b.zip", "a.zip"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
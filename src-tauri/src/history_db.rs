@@ -0,0 +1,973 @@
+use chrono::{Datelike, TimeZone};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::settings::{HistoryRetention, RetentionLimit};
+use crate::DownloadHistoryEntry;
+
+// One connection for the whole app, opened on first use. Holding the lock also
+// keeps an insert and the retention pass that follows it together.
+static DB: Mutex<Option<Connection>> = Mutex::new(None);
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        original_path TEXT NOT NULL,
+        extension TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        timestamp TEXT NOT NULL,
+        action TEXT NOT NULL,
+        destination TEXT,
+        trash_id TEXT
+    );
+    CREATE INDEX IF NOT EXISTS history_timestamp ON history(timestamp);
+    CREATE INDEX IF NOT EXISTS history_action ON history(action);
+    CREATE INDEX IF NOT EXISTS history_extension ON history(extension);
+    CREATE INDEX IF NOT EXISTS history_destination ON history(destination);
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+// DB_MIGRATIONS[n] upgrades a database at PRAGMA user_version n to n + 1.
+// SCHEMA stays as version 0 so every database goes through the same steps.
+const DB_MIGRATIONS: &[&str] = &[
+    "ALTER TABLE history ADD COLUMN detected_at TEXT",
+    "ALTER TABLE history ADD COLUMN content_hash TEXT;
+     CREATE INDEX IF NOT EXISTS history_content_hash ON history(content_hash);",
+    // The seen set (see seen.rs)
+    "CREATE TABLE seen (path TEXT PRIMARY KEY, size INTEGER NOT NULL, modified INTEGER);
+     CREATE TABLE seen_folders (path TEXT PRIMARY KEY, since TEXT NOT NULL);",
+    // The pending downloads inbox (see pending.rs)
+    "CREATE TABLE pending (
+         id INTEGER PRIMARY KEY AUTOINCREMENT,
+         path TEXT NOT NULL UNIQUE,
+         name TEXT NOT NULL,
+         size INTEGER NOT NULL,
+         detected_at TEXT NOT NULL,
+         duplicate_of TEXT,
+         queued_at TEXT NOT NULL,
+         snoozed_until TEXT
+     );",
+    // Moves used to record only the destination folder; they now record where the file
+    // ended up. Older rows get the name it most likely landed under.
+    "UPDATE history SET destination = CASE
+         WHEN substr(destination, -1) IN ('/', '\\') THEN destination || name
+         WHEN instr(destination, '\\') > 0 THEN destination || '\\' || name
+         ELSE destination || '/' || name
+     END
+     WHERE action = 'moved' AND destination IS NOT NULL;",
+    // Snoozes were local times, which clock changes can wake up to an hour early or late.
+    // SQLite converts from the system's time zone, as chrono does for new snoozes.
+    "UPDATE pending SET snoozed_until = datetime(snoozed_until, 'utc') WHERE snoozed_until IS NOT NULL;",
+];
+
+// The folder part of a recorded path, for either separator. Moves and renames record
+// the file itself.
+const DESTINATION_FOLDER: &str = "CASE WHEN action IN ('moved', 'renamed')
+    THEN rtrim(rtrim(destination, replace(replace(destination, '\\', ''), '/', '')), '/\\')
+    ELSE destination END";
+
+const COLUMNS: &str = "name, original_path, size, timestamp, action, destination, trash_id, detected_at, content_hash";
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum HistorySort {
+    #[default]
+    Timestamp,
+    Name,
+    Size,
+    Action,
+}
+
+// Every filter is optional. Dates are "YYYY-MM-DD" or full "YYYY-MM-DD HH:MM:SS"
+// timestamps, and both ends of the range are inclusive.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub action: Option<String>,
+    // Case-insensitive substring of the file name
+    pub name: Option<String>,
+    pub extension: Option<String>,
+    pub destination: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub sort: HistorySort,
+    // Newest / largest first unless set
+    pub ascending: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct HistoryPage {
+    pub entries: Vec<DownloadHistoryEntry>,
+    // Matches across all pages, for the pager
+    pub total: usize,
+}
+
+#[derive(Serialize)]
+pub struct PeriodTotals {
+    // "2026-01-05" for days, "2026-W02" for ISO weeks
+    pub period: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct GroupTotals {
+    pub key: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct ActionShare {
+    pub action: String,
+    pub files: u64,
+    // Share of all files in the range, 0.0 - 1.0
+    pub ratio: f64,
+}
+
+// Aggregates over the history still in the database (archived months aren't included)
+#[derive(Serialize)]
+pub struct HistoryStats {
+    pub files: u64,
+    pub bytes: u64,
+    pub per_day: Vec<PeriodTotals>,
+    pub per_week: Vec<PeriodTotals>,
+    pub top_destinations: Vec<GroupTotals>,
+    // Files without an extension are grouped under ""
+    pub extensions: Vec<GroupTotals>,
+    pub actions: Vec<ActionShare>,
+    // Only entries whose arrival the watcher saw have a detected_at to measure from
+    pub average_decision_secs: Option<f64>,
+}
+
+fn extension_of(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<DownloadHistoryEntry> {
+    Ok(DownloadHistoryEntry {
+        name: row.get(0)?,
+        original_path: row.get(1)?,
+        size: row.get::<_, i64>(2)?.max(0) as u64,
+        timestamp: row.get(3)?,
+        action: row.get(4)?,
+        destination: row.get(5)?,
+        trash_id: row.get(6)?,
+        detected_at: row.get(7)?,
+        content_hash: row.get(8)?,
+    })
+}
+
+fn insert_row(conn: &Connection, entry: &DownloadHistoryEntry) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT INTO history (name, original_path, extension, size, timestamp, action, destination, trash_id, detected_at, content_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            entry.name,
+            entry.original_path,
+            extension_of(&entry.name),
+            entry.size as i64,
+            entry.timestamp,
+            entry.action,
+            entry.destination,
+            entry.trash_id,
+            entry.detected_at,
+            entry.content_hash,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn init_schema(conn: &mut Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(SCHEMA)?;
+
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (from, migration) in DB_MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", from + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// data.json used to hold the history. It is copied over once, then dropped from data.json.
+// Returns true if there was anything to copy.
+fn import_legacy_history(conn: &mut Connection, legacy: &[DownloadHistoryEntry]) -> Result<bool, String> {
+    let imported: Option<String> = conn
+        .query_row("SELECT value FROM meta WHERE key = 'legacy_history_imported'", [], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if imported.is_some() {
+        return Ok(false);
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    // Stored newest first, so insert in reverse to keep ids in chronological order
+    for entry in legacy.iter().rev() {
+        insert_row(&tx, entry).map_err(|e| e.to_string())?;
+    }
+    tx.execute(
+        "INSERT INTO meta (key, value) VALUES ('legacy_history_imported', ?1)",
+        params![chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()],
+    )
+    .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    if !legacy.is_empty() {
+        println!("Imported {} history entries into history.db", legacy.len());
+    }
+    Ok(!legacy.is_empty())
+}
+
+fn open(legacy: &[DownloadHistoryEntry]) -> Result<(Connection, bool), String> {
+    let path = crate::get_app_dir().join("history.db");
+    let mut conn = Connection::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    conn.pragma_update(None, "journal_mode", "WAL").map_err(|e| e.to_string())?;
+    init_schema(&mut conn).map_err(|e| format!("Failed to set up {}: {}", path.display(), e))?;
+    let imported = import_legacy_history(&mut conn, legacy)?;
+    Ok((conn, imported))
+}
+
+// Also used by seen.rs, which keeps its tables in the same database
+pub fn with_db<T>(f: impl FnOnce(&mut Connection) -> Result<T, String>) -> Result<T, String> {
+    let lock = || DB.lock().unwrap_or_else(|e| e.into_inner());
+
+    // data.json is read (and later rewritten) without holding the database lock, so no
+    // history query ever waits on settings I/O
+    let legacy = if lock().is_none() {
+        Some(crate::load_app_data()?.download_history)
+    } else {
+        None
+    };
+
+    let mut guard = lock();
+    let mut imported = false;
+    if guard.is_none() {
+        let (conn, did_import) = open(legacy.as_deref().unwrap_or_default())?;
+        *guard = Some(conn);
+        imported = did_import;
+    }
+    let result = f(guard.as_mut().unwrap());
+    drop(guard);
+
+    if imported {
+        let mut data = crate::load_app_data()?;
+        data.download_history.clear();
+        crate::save_app_data(&data)?;
+    }
+    result
+}
+
+pub fn sql_err(e: rusqlite::Error) -> String {
+    format!("History database error: {}", e)
+}
+
+// "action = ?" for actions with their own limit, or "not one of those" for the default bucket
+fn bucket_filter(action: Option<&str>, retention: &HistoryRetention) -> (String, Vec<SqlValue>) {
+    match action {
+        Some(action) => ("action = ?".to_string(), vec![SqlValue::Text(action.to_string())]),
+        None if retention.per_action.is_empty() => ("1 = 1".to_string(), Vec::new()),
+        None => {
+            let placeholders = vec!["?"; retention.per_action.len()].join(", ");
+            let values = retention.per_action.keys().map(|a| SqlValue::Text(a.clone())).collect();
+            (format!("action NOT IN ({})", placeholders), values)
+        }
+    }
+}
+
+fn rolled_off_ids(conn: &Connection, filter: &str, values: &[SqlValue], limit: &RetentionLimit) -> rusqlite::Result<Vec<i64>> {
+    let mut ids = Vec::new();
+
+    if let Some(max) = limit.max_entries {
+        let sql = format!(
+            "SELECT id FROM history WHERE {} ORDER BY timestamp DESC, id DESC LIMIT -1 OFFSET {}",
+            filter, max
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values.iter()), |row| row.get(0))?;
+        ids.extend(rows.collect::<rusqlite::Result<Vec<i64>>>()?);
+    }
+
+    if let Some(days) = limit.max_age_days {
+        let cutoff = (chrono::Local::now() - chrono::Duration::days(i64::from(days)))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        let sql = format!("SELECT id FROM history WHERE {} AND timestamp < ?", filter);
+        let mut stmt = conn.prepare(&sql)?;
+        let mut bound = values.to_vec();
+        bound.push(SqlValue::Text(cutoff));
+        let rows = stmt.query_map(params_from_iter(bound.iter()), |row| row.get(0))?;
+        ids.extend(rows.collect::<rusqlite::Result<Vec<i64>>>()?);
+    }
+    Ok(ids)
+}
+
+// Deletes whatever is over the configured limits, archiving it first when enabled.
+// Actions listed in per_action are counted separately; everything else shares the default limit.
+fn apply_retention_with(conn: &mut Connection, retention: &HistoryRetention, archive: impl FnOnce(&[DownloadHistoryEntry]) -> Result<(), String>) -> Result<(), String> {
+    let mut ids = Vec::new();
+    let mut buckets: Vec<(Option<&str>, &RetentionLimit)> = vec![(None, &retention.default)];
+    buckets.extend(retention.per_action.iter().map(|(action, limit)| (Some(action.as_str()), limit)));
+    for (action, limit) in buckets {
+        let (filter, values) = bucket_filter(action, retention);
+        ids.extend(rolled_off_ids(conn, &filter, &values, limit).map_err(sql_err)?);
+    }
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn.transaction().map_err(sql_err)?;
+    let mut rolled_off = Vec::with_capacity(ids.len());
+    {
+        let mut select = tx
+            .prepare(&format!("SELECT {} FROM history WHERE id = ?1", COLUMNS))
+            .map_err(sql_err)?;
+        let mut delete = tx.prepare("DELETE FROM history WHERE id = ?1").map_err(sql_err)?;
+        for id in &ids {
+            if let Some(entry) = select.query_row([id], entry_from_row).optional().map_err(sql_err)? {
+                rolled_off.push(entry);
+            }
+            delete.execute([id]).map_err(sql_err)?;
+        }
+    }
+
+    // Archive before committing so a failed write leaves the rows in place. Should the
+    // commit fail instead, they're archived again later and read back only once.
+    if retention.archive {
+        archive(&rolled_off)?;
+    }
+    tx.commit().map_err(sql_err)
+}
+
+fn push_date_range(from: Option<&str>, to: Option<&str>, clauses: &mut Vec<&str>, values: &mut Vec<SqlValue>) {
+    if let Some(from) = from {
+        clauses.push("timestamp >= ?");
+        values.push(SqlValue::Text(from.to_string()));
+    }
+    if let Some(to) = to {
+        // A bare date covers the whole day
+        let to = if to.len() == 10 { format!("{} 23:59:59", to) } else { to.to_string() };
+        clauses.push("timestamp <= ?");
+        values.push(SqlValue::Text(to));
+    }
+}
+
+fn where_clause(clauses: &[&str]) -> String {
+    if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    }
+}
+
+fn query_with(conn: &Connection, query: &HistoryQuery) -> rusqlite::Result<HistoryPage> {
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+
+    push_date_range(query.from.as_deref(), query.to.as_deref(), &mut clauses, &mut values);
+    if let Some(action) = &query.action {
+        clauses.push("action = ?");
+        values.push(SqlValue::Text(action.clone()));
+    }
+    if let Some(name) = &query.name {
+        let escaped = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        clauses.push("name LIKE ? ESCAPE '\\'");
+        values.push(SqlValue::Text(format!("%{}%", escaped)));
+    }
+    if let Some(extension) = &query.extension {
+        clauses.push("extension = ?");
+        values.push(SqlValue::Text(extension.trim_start_matches('.').to_lowercase()));
+    }
+    if let Some(destination) = &query.destination {
+        clauses.push("destination = ?");
+        values.push(SqlValue::Text(destination.clone()));
+    }
+    if let Some(min) = query.min_size {
+        clauses.push("size >= ?");
+        values.push(SqlValue::Integer(min as i64));
+    }
+    if let Some(max) = query.max_size {
+        clauses.push("size <= ?");
+        values.push(SqlValue::Integer(max as i64));
+    }
+
+    let filter = where_clause(&clauses);
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM history {}", filter),
+        params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    let column = match query.sort {
+        HistorySort::Timestamp => "timestamp",
+        HistorySort::Name => "name COLLATE NOCASE",
+        HistorySort::Size => "size",
+        HistorySort::Action => "action",
+    };
+    let direction = if query.ascending { "ASC" } else { "DESC" };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    let sql = format!(
+        "SELECT {} FROM history {} ORDER BY {} {}, id {} LIMIT {} OFFSET {}",
+        COLUMNS, filter, column, direction, direction, limit, query.offset
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt
+        .query_map(params_from_iter(values.iter()), entry_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(HistoryPage {
+        entries,
+        total: total.max(0) as usize,
+    })
+}
+
+// Returns the new row's id
+pub fn insert(entry: &DownloadHistoryEntry, retention: &HistoryRetention) -> Result<i64, String> {
+    with_db(|conn| {
+        let id = insert_row(conn, entry).map_err(sql_err)?;
+        apply_retention_with(conn, retention, crate::history_archive::archive)?;
+        Ok(id)
+    })
+}
+
+// For hashes worked out after the entry was written. A row that retention has
+// already trimmed away is simply not there to update.
+pub fn set_content_hash(id: i64, hash: &str) -> Result<(), String> {
+    with_db(|conn| {
+        conn.execute("UPDATE history SET content_hash = ?1 WHERE id = ?2", params![hash, id])
+            .map_err(sql_err)?;
+        Ok(())
+    })
+}
+
+pub fn apply_retention(retention: &HistoryRetention) -> Result<(), String> {
+    with_db(|conn| apply_retention_with(conn, retention, crate::history_archive::archive))
+}
+
+pub fn query(query: &HistoryQuery) -> Result<HistoryPage, String> {
+    with_db(|conn| query_with(conn, query).map_err(sql_err))
+}
+
+// Everything, newest first
+pub fn all() -> Result<Vec<DownloadHistoryEntry>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM history ORDER BY timestamp DESC, id DESC", COLUMNS))
+            .map_err(sql_err)?;
+        let entries = stmt
+            .query_map([], entry_from_row)
+            .map_err(sql_err)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(sql_err)?;
+        Ok(entries)
+    })
+}
+
+// Moves and renames of files with this content, newest first
+pub fn organized_with_hash(hash: &str) -> Result<Vec<DownloadHistoryEntry>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM history WHERE content_hash = ?1 AND action IN ('moved', 'renamed')
+                 ORDER BY timestamp DESC, id DESC",
+                COLUMNS
+            ))
+            .map_err(sql_err)?;
+        let entries = stmt
+            .query_map(params![hash], entry_from_row)
+            .map_err(sql_err)?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(sql_err)?;
+        Ok(entries)
+    })
+}
+
+// trash id -> when it was deleted, for linking trash entries back to history
+pub fn deleted_timestamps() -> Result<HashMap<String, String>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT trash_id, timestamp FROM history WHERE trash_id IS NOT NULL")
+            .map_err(sql_err)?;
+        let pairs = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(sql_err)?
+            .collect::<rusqlite::Result<HashMap<_, _>>>()
+            .map_err(sql_err)?;
+        Ok(pairs)
+    })
+}
+
+// `tail` is the ORDER BY / LIMIT part, applied to the grouped key `k`
+fn group_totals(conn: &Connection, key: &str, filter: &str, values: &[SqlValue], tail: &str) -> rusqlite::Result<Vec<(String, u64, u64)>> {
+    let sql = format!(
+        "SELECT {} AS k, COUNT(*), COALESCE(SUM(size), 0) FROM history {} GROUP BY k {}",
+        key, filter, tail
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        Ok((
+            row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+            row.get::<_, i64>(1)?.max(0) as u64,
+            row.get::<_, i64>(2)?.max(0) as u64,
+        ))
+    })?;
+    rows.collect()
+}
+
+// ISO weeks start on Monday and belong to the year holding their Thursday, so the last
+// days of December can fall in week 1 of the next year. SQLite's %W can't do that.
+fn per_week(per_day: &[PeriodTotals]) -> Vec<PeriodTotals> {
+    let mut weeks: Vec<PeriodTotals> = Vec::new();
+    for day in per_day {
+        let Ok(date) = chrono::NaiveDate::parse_from_str(&day.period, "%Y-%m-%d") else {
+            continue;
+        };
+        let week = date.iso_week();
+        let period = format!("{:04}-W{:02}", week.year(), week.week());
+        // Days come in order, so a week's days are next to each other
+        match weeks.last_mut() {
+            Some(last) if last.period == period => {
+                last.files += day.files;
+                last.bytes += day.bytes;
+            }
+            _ => weeks.push(PeriodTotals {
+                period,
+                files: day.files,
+                bytes: day.bytes,
+            }),
+        }
+    }
+    weeks
+}
+
+// Timestamps are stored as local wall-clock time. Turning them into real instants keeps a
+// daylight saving change in between from adding or taking away an hour.
+pub fn local_instant(timestamp: &str) -> Option<chrono::DateTime<chrono::Local>> {
+    let naive = chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S").ok()?;
+    // The hour repeated when clocks go back is ambiguous, so its first occurrence is used
+    chrono::Local.from_local_datetime(&naive).earliest()
+}
+
+fn stats_with(conn: &Connection, from: Option<&str>, to: Option<&str>, top: usize) -> rusqlite::Result<HistoryStats> {
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut values: Vec<SqlValue> = Vec::new();
+    push_date_range(from, to, &mut clauses, &mut values);
+    let filter = where_clause(&clauses);
+
+    let (files, bytes): (i64, i64) = conn.query_row(
+        &format!("SELECT COUNT(*), COALESCE(SUM(size), 0) FROM history {}", filter),
+        params_from_iter(values.iter()),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let files = files.max(0) as u64;
+
+    let periods = |key: &str| -> rusqlite::Result<Vec<PeriodTotals>> {
+        Ok(group_totals(conn, key, &filter, &values, "ORDER BY k")?
+            .into_iter()
+            .map(|(period, files, bytes)| PeriodTotals { period, files, bytes })
+            .collect())
+    };
+    let biggest_first = format!("ORDER BY COUNT(*) DESC, k LIMIT {}", top);
+    let groups = |key: &str, filter: &str| -> rusqlite::Result<Vec<GroupTotals>> {
+        Ok(group_totals(conn, key, filter, &values, &biggest_first)?
+            .into_iter()
+            .map(|(key, files, bytes)| GroupTotals { key, files, bytes })
+            .collect())
+    };
+
+    let mut destination_clauses = clauses.clone();
+    destination_clauses.push("destination IS NOT NULL");
+
+    let actions = group_totals(conn, "action", &filter, &values, "ORDER BY COUNT(*) DESC, k")?
+        .into_iter()
+        .map(|(action, count, _)| ActionShare {
+            action,
+            files: count,
+            ratio: if files == 0 { 0.0 } else { count as f64 / files as f64 },
+        })
+        .collect();
+
+    let mut decision_clauses = clauses.clone();
+    decision_clauses.push("detected_at IS NOT NULL");
+    let mut waits: Vec<i64> = Vec::new();
+    {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT detected_at, timestamp FROM history {}",
+            where_clause(&decision_clauses)
+        ))?;
+        let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (detected_at, handled_at) = row?;
+            if let (Some(detected), Some(handled)) = (local_instant(&detected_at), local_instant(&handled_at)) {
+                waits.push((handled - detected).num_seconds().max(0));
+            }
+        }
+    }
+    let average_decision_secs = (!waits.is_empty()).then(|| waits.iter().sum::<i64>() as f64 / waits.len() as f64);
+
+    let per_day = periods("substr(timestamp, 1, 10)")?;
+    Ok(HistoryStats {
+        files,
+        bytes: bytes.max(0) as u64,
+        per_week: per_week(&per_day),
+        per_day,
+        top_destinations: groups(DESTINATION_FOLDER, &where_clause(&destination_clauses))?,
+        extensions: groups("extension", &filter)?,
+        actions,
+        average_decision_secs,
+    })
+}
+
+pub fn stats(from: Option<&str>, to: Option<&str>, top: usize) -> Result<HistoryStats, String> {
+    with_db(|conn| stats_with(conn, from, to, top).map_err(sql_err))
+}
+
+// Adds entries that aren't already recorded, matching on (original path, timestamp).
+// Returns how many were added and how many were skipped as duplicates. `write_rest`
+// saves the rest of an import; if it fails, none of the history goes in either.
+fn import_with(conn: &mut Connection, entries: &[DownloadHistoryEntry], replace: bool, write_rest: impl FnOnce() -> Result<(), String>) -> Result<(usize, usize), String> {
+    let tx = conn.transaction().map_err(sql_err)?;
+    if replace {
+        tx.execute("DELETE FROM history", []).map_err(sql_err)?;
+    }
+
+    let (mut added, mut skipped) = (0, 0);
+    {
+        let mut exists = tx
+            .prepare("SELECT 1 FROM history WHERE timestamp = ?1 AND original_path = ?2")
+            .map_err(sql_err)?;
+        for entry in entries.iter().rev() {
+            if exists.exists(params![entry.timestamp, entry.original_path]).map_err(sql_err)? {
+                skipped += 1;
+            } else {
+                insert_row(&tx, entry).map_err(sql_err)?;
+                added += 1;
+            }
+        }
+    }
+    write_rest()?;
+    tx.commit().map_err(sql_err)?;
+    Ok((added, skipped))
+}
+
+pub fn import(entries: &[DownloadHistoryEntry], replace: bool, retention: &HistoryRetention, write_rest: impl FnOnce() -> Result<(), String>) -> Result<(usize, usize), String> {
+    with_db(|conn| {
+        let counts = import_with(conn, entries, replace, write_rest)?;
+        apply_retention_with(conn, retention, crate::history_archive::archive)?;
+        Ok(counts)
+    })
+}
+
+pub fn clear() -> Result<(), String> {
+    with_db(|conn| conn.execute("DELETE FROM history", []).map(|_| ()).map_err(sql_err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64, timestamp: &str, action: &str) -> DownloadHistoryEntry {
+        DownloadHistoryEntry {
+            name: name.to_string(),
+            original_path: format!("/downloads/{}", name),
+            size,
+            timestamp: timestamp.to_string(),
+            action: action.to_string(),
+            destination: None,
+            trash_id: None,
+            detected_at: None,
+            content_hash: None,
+        }
+    }
+
+    fn test_db() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&mut conn).unwrap();
+        for e in [
+            entry("Report.PDF", 2_000, "2026-01-05 09:00:00", "moved"),
+            entry("photo_1.jpg", 500, "2026-01-10 12:30:00", "kept"),
+            entry("photo%2.jpg", 800, "2026-01-10 18:00:00", "deleted"),
+            entry("setup.exe", 90_000, "2026-02-01 08:00:00", "moved"),
+        ] {
+            insert_row(&conn, &e).unwrap();
+        }
+        conn
+    }
+
+    fn names(page: &HistoryPage) -> Vec<&str> {
+        page.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn query_filters_and_sorts() {
+        let conn = test_db();
+
+        let page = query_with(&conn, &HistoryQuery::default()).unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(names(&page), ["setup.exe", "photo%2.jpg", "photo_1.jpg", "Report.PDF"]);
+
+        let january = HistoryQuery {
+            from: Some("2026-01-06".to_string()),
+            to: Some("2026-01-10".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(&query_with(&conn, &january).unwrap()), ["photo%2.jpg", "photo_1.jpg"]);
+
+        let literal_percent = HistoryQuery {
+            name: Some("O%2".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(&query_with(&conn, &literal_percent).unwrap()), ["photo%2.jpg"]);
+
+        let by_size = HistoryQuery {
+            min_size: Some(600),
+            max_size: Some(5_000),
+            sort: HistorySort::Size,
+            ascending: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&query_with(&conn, &by_size).unwrap()), ["photo%2.jpg", "Report.PDF"]);
+
+        let pdfs = HistoryQuery {
+            extension: Some(".pdf".to_string()),
+            action: Some("moved".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(&query_with(&conn, &pdfs).unwrap()), ["Report.PDF"]);
+    }
+
+    #[test]
+    fn query_paginates() {
+        let conn = test_db();
+        let page = query_with(&conn, &HistoryQuery {
+            sort: HistorySort::Name,
+            ascending: true,
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(names(&page), ["photo_1.jpg", "Report.PDF"]);
+    }
+
+    #[test]
+    fn retention_uses_separate_buckets() {
+        let mut conn = test_db();
+        let mut retention = HistoryRetention::default();
+        retention.default.max_entries = Some(1);
+        retention.per_action.insert(
+            "kept".to_string(),
+            RetentionLimit {
+                max_entries: Some(5),
+                max_age_days: None,
+            },
+        );
+
+        let mut archived = Vec::new();
+        apply_retention_with(&mut conn, &retention, |entries| {
+            archived.extend(entries.iter().map(|e| e.name.clone()));
+            Ok(())
+        })
+        .unwrap();
+
+        let remaining = query_with(&conn, &HistoryQuery::default()).unwrap();
+        assert_eq!(names(&remaining), ["setup.exe", "photo_1.jpg"]);
+        assert_eq!(archived, ["Report.PDF", "photo%2.jpg"]);
+    }
+
+    #[test]
+    fn failed_archive_keeps_rows() {
+        let mut conn = test_db();
+        let mut retention = HistoryRetention::default();
+        retention.default.max_entries = Some(0);
+
+        let result = apply_retention_with(&mut conn, &retention, |_| Err("disk full".to_string()));
+        assert!(result.is_err());
+        assert_eq!(query_with(&conn, &HistoryQuery::default()).unwrap().total, 4);
+    }
+
+    #[test]
+    fn moves_recorded_as_folders_get_the_file_name() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        // Just before the migration that fills in file names
+        for migration in &DB_MIGRATIONS[..4] {
+            conn.execute_batch(migration).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 4).unwrap();
+        for (name, destination, action) in [
+            ("a.pdf", "C:\\Docs\\", "moved"),
+            ("b.pdf", "/home/me/docs", "moved"),
+            ("c.pdf", "/home/me/c2.pdf", "renamed"),
+        ] {
+            let mut row = entry(name, 1, "2026-01-01 10:00:00", action);
+            row.destination = Some(destination.to_string());
+            insert_row(&conn, &row).unwrap();
+        }
+
+        init_schema(&mut conn).unwrap();
+        let page = query_with(&conn, &HistoryQuery { ascending: true, ..Default::default() }).unwrap();
+        let destinations: Vec<&str> = page.entries.iter().filter_map(|e| e.destination.as_deref()).collect();
+        assert_eq!(destinations, ["C:\\Docs\\a.pdf", "/home/me/docs/b.pdf", "/home/me/c2.pdf"]);
+    }
+
+    #[test]
+    fn legacy_moves_imported_later_get_the_file_name() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&mut conn).unwrap();
+        // data.json and older exports are upgraded on load, before their history reaches the database
+        let load = |timestamp: &str| {
+            let mut value = serde_json::json!({
+                "download_history": [{
+                    "name": "a.pdf",
+                    "original_path": "/downloads/a.pdf",
+                    "size": 10,
+                    "timestamp": timestamp,
+                    "action": "moved",
+                    "destination": "/Docs",
+                    "trash_id": null
+                }]
+            });
+            crate::migrations::migrate(&mut value).unwrap();
+            serde_json::from_value::<crate::AppData>(value).unwrap().download_history
+        };
+
+        assert!(import_legacy_history(&mut conn, &load("2026-01-01 10:00:00")).unwrap());
+        assert_eq!(import_with(&mut conn, &load("2026-01-02 10:00:00"), false, || Ok(())).unwrap(), (1, 0));
+
+        let page = query_with(&conn, &HistoryQuery::default()).unwrap();
+        assert!(page.entries.iter().all(|e| e.destination.as_deref() == Some("/Docs/a.pdf")));
+        let stats = stats_with(&conn, None, None, 10).unwrap();
+        assert_eq!(stats.top_destinations.len(), 1);
+        assert_eq!(stats.top_destinations[0].key, "/Docs");
+        assert_eq!(stats.top_destinations[0].files, 2);
+    }
+
+    #[test]
+    fn weeks_span_the_new_year() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        init_schema(&mut conn).unwrap();
+        for timestamp in ["2025-12-29 09:00:00", "2025-12-31 23:00:00", "2026-01-01 08:00:00", "2026-01-04 20:00:00", "2027-01-01 10:00:00"] {
+            insert_row(&conn, &entry("a.pdf", 1, timestamp, "kept")).unwrap();
+        }
+
+        let stats = stats_with(&conn, None, None, 10).unwrap();
+        let weeks: Vec<(&str, u64)> = stats.per_week.iter().map(|p| (p.period.as_str(), p.files)).collect();
+        // Monday Dec 29 to Sunday Jan 4 is one week, and Jan 1 2027 still belongs to 2026
+        assert_eq!(weeks, [("2026-W01", 4), ("2026-W53", 1)]);
+    }
+
+    #[test]
+    fn snoozes_move_to_utc() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        for migration in &DB_MIGRATIONS[..5] {
+            conn.execute_batch(migration).unwrap();
+        }
+        conn.pragma_update(None, "user_version", 5).unwrap();
+        conn.execute(
+            "INSERT INTO pending (path, name, size, detected_at, queued_at, snoozed_until)
+             VALUES ('d/a.pdf', 'a.pdf', 1, '2026-05-01 09:00:00', '2026-05-01 09:00:00', '2026-07-01 12:00:00')",
+            [],
+        )
+        .unwrap();
+
+        init_schema(&mut conn).unwrap();
+        let stored: String = conn.query_row("SELECT snoozed_until FROM pending", [], |row| row.get(0)).unwrap();
+        let expected = local_instant("2026-07-01 12:00:00").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(stored, expected.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+
+    #[test]
+    fn stats_aggregate_history() {
+        let conn = test_db();
+        let mut decided = entry("notes.txt", 100, "2026-02-02 10:01:30", "moved");
+        decided.destination = Some("/docs/notes.txt".to_string());
+        decided.detected_at = Some("2026-02-02 10:00:00".to_string());
+        insert_row(&conn, &decided).unwrap();
+
+        let stats = stats_with(&conn, Some("2026-01-01"), None, 10).unwrap();
+        assert_eq!(stats.files, 5);
+        assert_eq!(stats.bytes, 93_400);
+
+        let days: Vec<(&str, u64)> = stats.per_day.iter().map(|p| (p.period.as_str(), p.files)).collect();
+        assert_eq!(days, [("2026-01-05", 1), ("2026-01-10", 2), ("2026-02-01", 1), ("2026-02-02", 1)]);
+        let weeks: Vec<(&str, u64)> = stats.per_week.iter().map(|p| (p.period.as_str(), p.files)).collect();
+        assert_eq!(weeks, [("2026-W02", 3), ("2026-W05", 1), ("2026-W06", 1)]);
+
+        assert_eq!(stats.extensions[0].key, "jpg");
+        assert_eq!(stats.extensions[0].bytes, 1_300);
+        assert_eq!(stats.top_destinations.len(), 1);
+        assert_eq!(stats.top_destinations[0].key, "/docs");
+
+        assert_eq!(stats.actions[0].action, "moved");
+        assert!((stats.actions[0].ratio - 0.6).abs() < 1e-9);
+        assert_eq!(stats.average_decision_secs, Some(90.0));
+
+        let february = stats_with(&conn, Some("2026-02-01"), Some("2026-02-01"), 10).unwrap();
+        assert_eq!(february.files, 1);
+        assert_eq!(february.average_decision_secs, None);
+    }
+
+    #[test]
+    fn renames_count_under_their_folder() {
+        let conn = test_db();
+        for (name, destination) in [("scan.png", "/downloads/2026-02-02 scan.png"), ("memo.txt", "/downloads/memo-final.txt")] {
+            let mut renamed = entry(name, 100, "2026-02-02 11:00:00", "renamed");
+            renamed.destination = Some(destination.to_string());
+            insert_row(&conn, &renamed).unwrap();
+        }
+
+        let stats = stats_with(&conn, None, None, 10).unwrap();
+        assert_eq!(stats.top_destinations.len(), 1);
+        assert_eq!(stats.top_destinations[0].key, "/downloads");
+        assert_eq!(stats.top_destinations[0].files, 2);
+    }
+
+    #[test]
+    fn import_skips_duplicates() {
+        let mut conn = test_db();
+        let incoming = [
+            entry("new.zip", 10, "2026-03-01 10:00:00", "moved"),
+            entry("Report.PDF", 2_000, "2026-01-05 09:00:00", "moved"),
+            entry("new.zip", 10, "2026-03-01 10:00:00", "moved"),
+        ];
+
+        assert_eq!(import_with(&mut conn, &incoming, false, || Ok(())).unwrap(), (1, 2));
+        assert_eq!(query_with(&conn, &HistoryQuery::default()).unwrap().total, 5);
+
+        // Nothing goes in when the rest of the import can't be saved
+        let failed = import_with(&mut conn, &incoming, true, || Err("disk full".to_string()));
+        assert_eq!(failed, Err("disk full".to_string()));
+        assert_eq!(query_with(&conn, &HistoryQuery::default()).unwrap().total, 5);
+
+        assert_eq!(import_with(&mut conn, &incoming, true, || Ok(())).unwrap(), (2, 1));
+        assert_eq!(query_with(&conn, &HistoryQuery::default()).unwrap().total, 2);
+    }
+}
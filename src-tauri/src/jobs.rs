@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Shared between a background job thread and the commands that steer it
+#[derive(Default)]
+pub struct JobControl {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+}
+
+impl JobControl {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    // Blocks while the job is paused. Returns false once it has been cancelled.
+    pub fn checkpoint(&self) -> bool {
+        while self.is_paused() && !self.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        !self.is_cancelled()
+    }
+}
+
+// Threads for a job's parallel work. A paused job parks them in checkpoint, which on
+// rayon's global pool would also hold up every other job using it.
+pub fn thread_pool(kind: &str) -> Result<rayon::ThreadPool, String> {
+    let name = kind.to_string();
+    rayon::ThreadPoolBuilder::new()
+        .thread_name(move |n| format!("{}-{}", name, n))
+        .build()
+        .map_err(|e| format!("Cannot start {} threads: {}", kind, e))
+}
+
+// Registered as Tauri state so any job type can be cancelled / paused by id
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, Arc<JobControl>>>,
+    next_id: AtomicU64,
+}
+
+impl JobRegistry {
+    // `kind` just makes ids readable in logs, e.g. "transfer-3"
+    pub fn start(&self, kind: &str) -> (String, Arc<JobControl>) {
+        let id = format!("{}-{}", kind, self.next_id.fetch_add(1, Ordering::SeqCst) + 1);
+        let control = Arc::new(JobControl::default());
+        self.lock().insert(id.clone(), control.clone());
+        (id, control)
+    }
+
+    pub fn finish(&self, id: &str) {
+        self.lock().remove(id);
+    }
+
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let control = self.get(id)?;
+        control.cancelled.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    pub fn set_paused(&self, id: &str, paused: bool) -> Result<(), String> {
+        let control = self.get(id)?;
+        control.paused.store(paused, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn get(&self, id: &str) -> Result<Arc<JobControl>, String> {
+        self.lock()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("No running job with id {}", id))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<JobControl>>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,515 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::trash_bin;
+
+// Oldest transactions fall off once the undo stack grows past this
+const MAX_TRANSACTIONS: usize = 100;
+
+// Commands run on several threads, so journal read-modify-writes are serialized
+static JOURNAL_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Operation {
+    Move {
+        from: String,
+        to: String,
+        // Folders that didn't exist before the move, outermost first
+        created_dirs: Vec<String>,
+    },
+    Delete {
+        path: String,
+        trash_id: Option<String>,
+    },
+    CreateFolder {
+        path: String,
+        created_dirs: Vec<String>,
+    },
+    // `to` is a fresh copy of `from`, journaled when it replaced something
+    Copy {
+        from: String,
+        to: String,
+    },
+    // `path` was an identical copy of `target` and became a hardlink to it
+    Hardlink {
+        path: String,
+        target: String,
+    },
+}
+
+// One undo step - a single operation, or every operation of a bulk action
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Transaction {
+    pub id: String,
+    pub timestamp: String,
+    pub operations: Vec<Operation>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Journal {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+}
+
+fn journal_path() -> PathBuf {
+    crate::get_app_dir().join("journal.json")
+}
+
+// Losing undo history isn't worth blocking file operations over, so errors are only logged.
+// A journal that can't be read is kept under another name before a fresh one replaces it;
+// None if it couldn't be moved aside, so nothing overwrites it.
+fn load_from(path: &Path) -> Option<Journal> {
+    let error = match crate::storage::read_json(path) {
+        Ok(journal) => return Some(journal.unwrap_or_default()),
+        Err(e) => e,
+    };
+    eprintln!("Failed to read undo journal: {}", error);
+    // read_json moves a corrupt file aside itself once the backup fails too
+    if !path.exists() {
+        return Some(Journal::default());
+    }
+
+    let mut aside = path.as_os_str().to_os_string();
+    aside.push(format!(".corrupt-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    match fs::rename(path, &aside) {
+        Ok(()) => {
+            eprintln!("Kept the unreadable undo journal as {}", Path::new(&aside).display());
+            Some(Journal::default())
+        }
+        Err(e) => {
+            eprintln!("Cannot move the unreadable undo journal aside, leaving it untouched: {}", e);
+            None
+        }
+    }
+}
+
+fn load() -> Option<Journal> {
+    load_from(&journal_path())
+}
+
+fn save(journal: &Journal) {
+    let json = serde_json::to_string_pretty(journal).unwrap_or_default();
+    if let Err(e) = crate::storage::write_atomic(&journal_path(), json.as_bytes()) {
+        eprintln!("Failed to write undo journal: {}", e);
+    }
+}
+
+pub fn new_transaction_id() -> String {
+    chrono::Local::now().timestamp_nanos_opt().unwrap_or_default().to_string()
+}
+
+// Operations sharing a batch id (e.g. one multi-select move) are undone together
+pub fn record(operation: Operation, batch_id: Option<String>) {
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(mut journal) = load() else {
+        return;
+    };
+    record_in(&mut journal, operation, batch_id);
+    save(&journal);
+}
+
+// A batch stays open wherever it is in the stack, as the watcher or a background job can
+// record something of their own in the middle of a bulk action
+fn record_in(journal: &mut Journal, operation: Operation, batch_id: Option<String>) {
+    let open = batch_id
+        .as_ref()
+        .and_then(|id| journal.undo.iter_mut().rev().find(|transaction| &transaction.id == id));
+    match open {
+        Some(transaction) => transaction.operations.push(operation),
+        None => {
+            let id = batch_id.unwrap_or_else(new_transaction_id);
+            journal.undo.push(Transaction {
+                id,
+                timestamp: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                operations: vec![operation],
+            });
+            if journal.undo.len() > MAX_TRANSACTIONS {
+                let excess = journal.undo.len() - MAX_TRANSACTIONS;
+                journal.undo.drain(..excess);
+            }
+        }
+    }
+
+    // A new action invalidates anything that was undone before it
+    journal.redo.clear();
+}
+
+// Removes folders we created, innermost first. Non-empty ones are left alone.
+fn remove_created_dirs(created_dirs: &[String]) {
+    for dir in created_dirs.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+}
+
+// Each returns the operation as it should be stored afterwards (e.g. a fresh trash id)
+fn undo_operation(operation: &Operation) -> Result<Operation, String> {
+    match operation {
+        Operation::Move { from, to, created_dirs } => {
+            if !Path::new(to).exists() {
+                return Err(format!("{} no longer exists", to));
+            }
+            if Path::new(from).exists() {
+                return Err(format!("{} already exists", from));
+            }
+            // The item is back either way, so leftovers of it don't fail the undo
+            if let Some(leftovers) = crate::move_path(to, from)?.leftovers {
+                eprintln!("{}", leftovers);
+            }
+            remove_created_dirs(created_dirs);
+        }
+        Operation::Delete { path, trash_id } => {
+            let id = trash_id
+                .as_ref()
+                .ok_or_else(|| format!("{} was not found in the trash", path))?;
+            trash_bin::restore(id)?;
+        }
+        Operation::CreateFolder { path, created_dirs } => {
+            fs::remove_dir(path).map_err(|e| format!("Cannot remove {}: {}", path, e))?;
+            remove_created_dirs(created_dirs);
+        }
+        Operation::Copy { to, .. } => {
+            // Goes before anything it replaced is restored, as undo runs in reverse
+            let path = Path::new(to);
+            let removed = if fs::symlink_metadata(path).map(|m| m.is_dir()).unwrap_or(false) {
+                fs::remove_dir_all(path)
+            } else {
+                fs::remove_file(path)
+            };
+            removed.map_err(|e| format!("Cannot remove {}: {}", to, e))?;
+        }
+        Operation::Hardlink { path, target } => {
+            crate::duplicates::break_hardlink(Path::new(target), Path::new(path))?;
+        }
+    }
+    Ok(operation.clone())
+}
+
+fn redo_operation(operation: &Operation) -> Result<Operation, String> {
+    match operation {
+        Operation::Move { from, to, .. } => {
+            if !Path::new(from).exists() {
+                return Err(format!("{} no longer exists", from));
+            }
+            if Path::new(to).exists() {
+                return Err(format!("{} already exists", to));
+            }
+            let moved = crate::move_path(from, to)?;
+            if let Some(leftovers) = moved.leftovers {
+                eprintln!("{}", leftovers);
+            }
+            Ok(Operation::Move {
+                from: from.clone(),
+                to: to.clone(),
+                created_dirs: moved.created_dirs,
+            })
+        }
+        Operation::Delete { path, .. } => {
+            trash::delete(path).map_err(|e| e.to_string())?;
+            Ok(Operation::Delete {
+                path: path.clone(),
+                trash_id: trash_bin::find_trash_id(Path::new(path)),
+            })
+        }
+        Operation::CreateFolder { path, .. } => {
+            let created_dirs = crate::create_dirs_tracked(Path::new(path))?;
+            Ok(Operation::CreateFolder {
+                path: path.clone(),
+                created_dirs,
+            })
+        }
+        Operation::Copy { from, to } => {
+            if !Path::new(from).exists() {
+                return Err(format!("{} no longer exists", from));
+            }
+            if Path::new(to).exists() {
+                return Err(format!("{} already exists", to));
+            }
+            if Path::new(from).is_dir() {
+                crate::transfer::copy_tree(Path::new(from), Path::new(to), &mut |file, copy| {
+                    crate::transfer::copy_file_preserving(file, copy).map(|_| true)
+                })?;
+            } else {
+                crate::transfer::copy_file_preserving(Path::new(from), Path::new(to))?;
+            }
+            Ok(operation.clone())
+        }
+        Operation::Hardlink { path, target } => {
+            // Linking throws away what's at `path`, so only while it's still the same content
+            if crate::duplicates::hash_file(Path::new(path))? != crate::duplicates::hash_file(Path::new(target))? {
+                return Err(format!("{} no longer matches {}", path, target));
+            }
+            crate::duplicates::replace_with_hardlink(Path::new(target), Path::new(path))?;
+            Ok(operation.clone())
+        }
+    }
+}
+
+// All-or-nothing: if one operation fails, the ones already replayed are rolled back
+fn run_transaction(transaction: &mut Transaction, undo: bool) -> Result<(), String> {
+    let count = transaction.operations.len();
+    let order: Vec<usize> = if undo {
+        (0..count).rev().collect()
+    } else {
+        (0..count).collect()
+    };
+
+    let mut done: Vec<usize> = Vec::new();
+    for index in order {
+        let result = if undo {
+            undo_operation(&transaction.operations[index])
+        } else {
+            redo_operation(&transaction.operations[index])
+        };
+
+        match result {
+            Ok(updated) => {
+                transaction.operations[index] = updated;
+                done.push(index);
+            }
+            Err(e) => {
+                for &finished in done.iter().rev() {
+                    let rollback = if undo {
+                        redo_operation(&transaction.operations[finished])
+                    } else {
+                        undo_operation(&transaction.operations[finished])
+                    };
+                    match rollback {
+                        Ok(updated) => transaction.operations[finished] = updated,
+                        Err(rollback_err) => eprintln!("Rollback failed: {}", rollback_err),
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Replays the newest transaction of one stack and moves it onto the other. A failed
+// transaction stays where it was, already rolled back.
+fn step(journal: &mut Journal, undo: bool) -> Result<Option<Transaction>, String> {
+    let (from, to) = if undo {
+        (&mut journal.undo, &mut journal.redo)
+    } else {
+        (&mut journal.redo, &mut journal.undo)
+    };
+
+    let mut transaction = match from.pop() {
+        Some(transaction) => transaction,
+        None => return Ok(None),
+    };
+
+    let result = run_transaction(&mut transaction, undo);
+    if result.is_ok() {
+        to.push(transaction.clone());
+    } else {
+        from.push(transaction.clone());
+    }
+    result.map(|_| Some(transaction))
+}
+
+pub fn undo_last() -> Result<Option<Transaction>, String> {
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut journal = load().ok_or("The undo history could not be read")?;
+    let result = step(&mut journal, true);
+    save(&journal);
+    result
+}
+
+pub fn redo() -> Result<Option<Transaction>, String> {
+    let _guard = JOURNAL_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut journal = load().ok_or("The undo history could not be read")?;
+    let result = step(&mut journal, false);
+    save(&journal);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fileforge-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    // Moves `name` from root/in to root/out/sub like move_file does, and journals it
+    fn move_into(journal: &mut Journal, root: &Path, name: &str, batch_id: Option<&str>) -> (String, String) {
+        let from = root.join("in").join(name).to_string_lossy().to_string();
+        let to = root.join("out/sub").join(name).to_string_lossy().to_string();
+        let created_dirs = crate::move_path(&from, &to).unwrap().created_dirs;
+        let operation = Operation::Move { from: from.clone(), to: to.clone(), created_dirs };
+        record_in(journal, operation, batch_id.map(str::to_string));
+        (from, to)
+    }
+
+    #[test]
+    fn unreadable_journal_is_kept() {
+        let root = scratch("unreadable");
+        let path = root.join("journal.json");
+        fs::write(&path, "{ not json").unwrap();
+
+        let journal = load_from(&path).unwrap();
+        assert!(journal.undo.is_empty());
+        let kept: Vec<PathBuf> = fs::read_dir(&root).unwrap().map(|e| e.unwrap().path()).collect();
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].to_string_lossy().contains("journal.json.corrupt-"));
+        assert_eq!(fs::read_to_string(&kept[0]).unwrap(), "{ not json");
+
+        // A good backup is used instead of starting over
+        let mut saved = Journal::default();
+        record_in(&mut saved, Operation::Delete { path: "a".to_string(), trash_id: None }, None);
+        fs::write(&path, "{ not json").unwrap();
+        fs::write(crate::storage::backup_path(&path), serde_json::to_string(&saved).unwrap()).unwrap();
+        assert_eq!(load_from(&path).unwrap().undo.len(), 1);
+
+        // Can't be read at all (not just corrupt) - moved aside here rather than by read_json
+        let unreadable = scratch("unreadable-dir");
+        let path = unreadable.join("journal.json");
+        fs::create_dir(&path).unwrap();
+        assert!(load_from(&path).unwrap().undo.is_empty());
+        assert!(!path.exists());
+        assert_eq!(fs::read_dir(&unreadable).unwrap().count(), 1);
+
+        fs::remove_dir_all(&unreadable).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn setup(name: &str, files: &[&str]) -> PathBuf {
+        let root = scratch(name);
+        fs::create_dir_all(root.join("in")).unwrap();
+        for file in files {
+            fs::write(root.join("in").join(file), file).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn batch_undoes_and_redoes_as_one_step() {
+        let root = setup("batch", &["a.txt", "b.txt", "c.txt"]);
+        let mut journal = Journal::default();
+
+        move_into(&mut journal, &root, "a.txt", Some("bulk"));
+        move_into(&mut journal, &root, "b.txt", Some("bulk"));
+        move_into(&mut journal, &root, "c.txt", None);
+        assert_eq!(journal.undo.len(), 2);
+        assert_eq!(journal.undo[0].operations.len(), 2);
+
+        step(&mut journal, true).unwrap();
+        let undone = step(&mut journal, true).unwrap().unwrap();
+        assert_eq!(undone.id, "bulk");
+        assert!(root.join("in/a.txt").exists() && root.join("in/b.txt").exists());
+        // The folders the moves created went with them
+        assert!(!root.join("out").exists());
+        assert!(step(&mut journal, true).unwrap().is_none());
+
+        step(&mut journal, false).unwrap();
+        assert!(root.join("out/sub/a.txt").exists() && root.join("out/sub/b.txt").exists());
+        assert_eq!((journal.undo.len(), journal.redo.len()), (1, 1));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unbatched_operation_does_not_split_a_batch() {
+        let root = setup("interleaved", &["a.txt", "b.txt", "c.txt"]);
+        let mut journal = Journal::default();
+
+        move_into(&mut journal, &root, "a.txt", Some("bulk"));
+        // e.g. a rule moving a new download while the bulk move is still going
+        move_into(&mut journal, &root, "c.txt", None);
+        move_into(&mut journal, &root, "b.txt", Some("bulk"));
+        assert_eq!(journal.undo.len(), 2);
+        assert_eq!(journal.undo[0].id, "bulk");
+        assert_eq!(journal.undo[0].operations.len(), 2);
+
+        step(&mut journal, true).unwrap();
+        assert!(root.join("in/c.txt").exists());
+        let undone = step(&mut journal, true).unwrap().unwrap();
+        assert_eq!(undone.id, "bulk");
+        assert!(root.join("in/a.txt").exists() && root.join("in/b.txt").exists());
+        assert!(!root.join("out").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn failed_step_rolls_back_the_whole_batch() {
+        let root = setup("rollback", &["a.txt", "b.txt"]);
+        let mut journal = Journal::default();
+        move_into(&mut journal, &root, "a.txt", Some("bulk"));
+        move_into(&mut journal, &root, "b.txt", Some("bulk"));
+
+        // b is undone first, then a can't go back because something took its place
+        fs::write(root.join("in/a.txt"), "newer").unwrap();
+        assert!(step(&mut journal, true).is_err());
+
+        assert!(root.join("out/sub/b.txt").exists(), "b should be moved back out again");
+        assert!(!root.join("in/b.txt").exists());
+        assert_eq!(fs::read_to_string(root.join("in/a.txt")).unwrap(), "newer");
+        assert_eq!((journal.undo.len(), journal.redo.len()), (1, 0));
+
+        // Once the way is clear the same step goes through
+        fs::remove_file(root.join("in/a.txt")).unwrap();
+        step(&mut journal, true).unwrap();
+        assert_eq!(fs::read_to_string(root.join("in/a.txt")).unwrap(), "a.txt");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn new_action_clears_redo() {
+        let root = setup("redo", &["a.txt", "b.txt"]);
+        let mut journal = Journal::default();
+        move_into(&mut journal, &root, "a.txt", None);
+        step(&mut journal, true).unwrap();
+        assert_eq!(journal.redo.len(), 1);
+
+        let folder = root.join("made/here");
+        let created_dirs = crate::create_dirs_tracked(&folder).unwrap();
+        let path = folder.to_string_lossy().to_string();
+        record_in(&mut journal, Operation::CreateFolder { path, created_dirs }, None);
+        assert!(journal.redo.is_empty());
+        assert!(step(&mut journal, false).unwrap().is_none());
+
+        // Undoing the new folder removes it and the parent it needed
+        step(&mut journal, true).unwrap();
+        assert!(!root.join("made").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn undoing_a_replacing_copy_puts_the_old_item_back() {
+        let root = setup("copy-replace", &["a.txt"]);
+        fs::create_dir_all(root.join("out")).unwrap();
+        fs::write(root.join("out/a.txt"), "old").unwrap();
+        let mut journal = Journal::default();
+
+        let path = |name: &str| root.join(name).to_string_lossy().to_string();
+        let (from, to, aside) = (path("in/a.txt"), path("out/a.txt"), path("aside.txt"));
+
+        // A move aside stands in for the trash, so the real one is left alone
+        fs::rename(&to, &aside).unwrap();
+        let trashed = Operation::Move { from: to.clone(), to: aside.clone(), created_dirs: Vec::new() };
+        record_in(&mut journal, trashed, Some("copy".to_string()));
+        crate::transfer::copy_file_preserving(Path::new(&from), Path::new(&to)).unwrap();
+        record_in(&mut journal, Operation::Copy { from: from.clone(), to: to.clone() }, Some("copy".to_string()));
+
+        step(&mut journal, true).unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "old");
+        assert_eq!(fs::read_to_string(&from).unwrap(), "a.txt");
+        assert!(!Path::new(&aside).exists());
+
+        step(&mut journal, false).unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "a.txt");
+        assert_eq!(fs::read_to_string(&aside).unwrap(), "old");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -2,9 +2,7 @@ use serde::Serialize;
 use sysinfo::Disks;
 use std::fs;
 use std::path::Path;
-use notify::{Watcher, RecursiveMode, Event, EventKind};
-use std::sync::mpsc::channel;
-use tauri::{Emitter, Manager, AppHandle};
+use tauri::{Emitter, Manager, AppHandle, State};
 use tauri::tray::{TrayIconBuilder, MouseButton, MouseButtonState, TrayIconEvent};
 use tauri::menu::{Menu, MenuItem};
 #[cfg(target_os = "windows")]
@@ -12,6 +10,31 @@ use winreg::enums::*;
 #[cfg(target_os = "windows")]
 use winreg::RegKey;
 
+mod backup;
+mod completion;
+mod conflicts;
+mod debounce;
+mod content_search;
+mod disk_usage;
+mod duplicates;
+mod file_details;
+mod file_index;
+mod history_archive;
+mod history_db;
+mod jobs;
+mod journal;
+mod listing;
+mod migrations;
+mod pending;
+mod redownload;
+mod rules;
+mod seen;
+mod settings;
+mod storage;
+mod transfer;
+mod trash_bin;
+mod watcher;
+
 // Basic input validation - only blocks obviously malicious input
 fn basic_path_check(path: &str) -> Result<(), String> {
     // Block NULL bytes (never legitimate in paths)
@@ -65,6 +88,9 @@ struct FileEntry {
     path: String,
     is_dir: bool,
     size: u64,
+    // Only filled in when the caller asks for details
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<file_details::FileDetails>,
 }
 
 #[derive(Serialize, Clone)]
@@ -72,6 +98,10 @@ struct NewFileEvent {
     name: String,
     path: String,
     size: u64,
+    // When the watcher first saw the file, passed back to add_to_history
+    detected_at: String,
+    // Set when the same content was already moved or renamed and is still there
+    duplicate_of: Option<redownload::DuplicateOf>,
 }
 
 #[tauri::command]
@@ -104,8 +134,10 @@ fn get_drives() -> Vec<DriveInfo> {
         .collect()
 }
 
+// `details` adds timestamps, permissions, link targets and MIME types at the cost of
+// an extra read per file, so the plain listing stays cheap
 #[tauri::command]
-fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
+fn list_directory(path: String, details: Option<bool>) -> Result<Vec<FileEntry>, String> {
     // Basic validation - only blocks obvious attacks
     basic_path_check(&path)?;
 
@@ -127,6 +159,9 @@ fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
                 path: entry.path().to_string_lossy().to_string(),
                 is_dir: metadata.is_dir(),
                 size: metadata.len(),
+                details: details
+                    .unwrap_or(false)
+                    .then(|| file_details::gather(&entry.path(), &metadata)),
             })
         })
         .collect();
@@ -134,33 +169,157 @@ fn list_directory(path: String) -> Result<Vec<FileEntry>, String> {
     Ok(entries)
 }
 
+// Paged, sorted and filtered listing for big folders; list_directory stays for the simple views
 #[tauri::command]
-fn move_file(source: String, destination: String) -> Result<(), String> {
-    // Basic validation - only blocks obvious attacks
-    basic_path_check(&source)?;
-    basic_path_check(&destination)?;
+fn list_directory_ex(path: String, options: Option<listing::ListOptions>) -> Result<listing::DirectoryPage, String> {
+    basic_path_check(&path)?;
+    listing::list(Path::new(&path), &options.unwrap_or_default())
+}
+
+// Like create_dir_all, but reports which folders it actually created (outermost first)
+fn create_dirs_tracked(dir: &Path) -> Result<Vec<String>, String> {
+    let mut missing = Vec::new();
+    let mut current = Some(dir);
+    while let Some(d) = current {
+        if d.as_os_str().is_empty() || d.exists() {
+            break;
+        }
+        missing.push(d.to_string_lossy().to_string());
+        current = d.parent();
+    }
+
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    missing.reverse();
+    Ok(missing)
+}
+
+struct Moved {
+    created_dirs: Vec<String>,
+    // Set when a folder was copied over in full but some of the original couldn't be deleted
+    leftovers: Option<String>,
+}
 
-    let dest_path = Path::new(&destination);
+// Moves without journaling - undo/redo replay through this directly
+fn move_path(source: &str, destination: &str) -> Result<Moved, String> {
+    let source_path = Path::new(source);
+    let dest_path = Path::new(destination);
+    let is_dir = fs::symlink_metadata(source_path).map(|m| m.is_dir()).unwrap_or(false);
 
     // Create destination directory if it doesn't exist
-    if let Some(parent) = dest_path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+    let created_dirs = match dest_path.parent() {
+        Some(parent) => create_dirs_tracked(parent)?,
+        None => Vec::new(),
+    };
     
-    // Try rename first (fast, same drive)
-    if fs::rename(&source, &destination).is_ok() {
-        return Ok(());
+    // Try rename first (fast, same drive). Something may have taken the destination since
+    // the conflict check, and a plain rename would silently replace it on Unix.
+    match transfer::rename_no_replace(source_path, dest_path) {
+        Ok(()) => return Ok(Moved { created_dirs, leftovers: None }),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            return Err(format!("{} already exists", destination));
+        }
+        Err(_) => {}
     }
     
     // If rename fails (cross-drive), copy then delete
-    fs::copy(&source, &destination).map_err(|e| format!("Copy failed: {}", e))?;
-    fs::remove_file(&source).map_err(|e| format!("Delete original failed: {}", e))?;
-    
-    Ok(())
+    let moved = if is_dir {
+        transfer::move_tree(source_path, dest_path, &mut |from, to| {
+            transfer::copy_file_preserving(from, to).map(|_| true)
+        })
+    } else {
+        transfer::move_file_by_copy(source_path, dest_path).map(|_| None)
+    };
+    match moved {
+        Ok(leftovers) => Ok(Moved { created_dirs, leftovers }),
+        Err(e) => {
+            // Nothing was moved, so nothing should be left behind for undo to know about
+            for dir in created_dirs.iter().rev() {
+                let _ = fs::remove_dir(dir);
+            }
+            Err(e)
+        }
+    }
+}
+
+// Applies the conflict policy. Returns None when the item should be skipped, otherwise the
+// destination and whether an existing item there gets replaced.
+fn resolve_destination(source: &str, destination: &str, policy: conflicts::ConflictPolicy) -> Result<Option<(String, bool)>, String> {
+    let resolution = conflicts::resolve(Path::new(source), Path::new(destination), policy)?;
+    Ok(match resolution {
+        conflicts::Resolution::Skip => None,
+        conflicts::Resolution::Proceed(path) => Some((path.to_string_lossy().to_string(), false)),
+        conflicts::Resolution::Replace(path) => Some((path.to_string_lossy().to_string(), true)),
+    })
+}
+
+// Returns the final destination, or None if the conflict policy skipped it
+#[tauri::command]
+fn move_file(source: String, destination: String, batch_id: Option<String>, conflict: Option<conflicts::ConflictPolicy>) -> Result<Option<String>, String> {
+    // Basic validation - only blocks obvious attacks
+    basic_path_check(&source)?;
+    basic_path_check(&destination)?;
+
+    let (destination, replace) = match resolve_destination(&source, &destination, conflict.unwrap_or_default())? {
+        Some(resolved) => resolved,
+        None => return Ok(None),
+    };
+
+    // A move that left some of the original folder behind is still journaled like any
+    // other, with the leftovers reported as an error
+    if !replace {
+        let moved = move_path(&source, &destination)?;
+        journal::record(
+            journal::Operation::Move { from: source, to: destination.clone(), created_dirs: moved.created_dirs },
+            batch_id,
+        );
+        return match moved.leftovers {
+            Some(error) => Err(error),
+            None => Ok(Some(destination)),
+        };
+    }
+
+    // The new item waits beside the old one, so a failed move leaves the old one in place
+    let staged = conflicts::staging_path(Path::new(&destination)).to_string_lossy().to_string();
+    let leftovers = move_path(&source, &staged)?.leftovers;
+    let trashed = match conflicts::swap_into_place(Path::new(&staged), Path::new(&destination)) {
+        Ok(trashed) => trashed,
+        Err(e) => {
+            // With leftovers in the way the copy can't go back, so it stays where it is
+            if leftovers.is_some() {
+                return Err(format!("{} (the moved copy is at {})", e, staged));
+            }
+            if let Err(back) = move_path(&staged, &source) {
+                eprintln!("Could not move {} back to {}: {}", staged, source, back);
+            }
+            return Err(e);
+        }
+    };
+
+    // The replaced item goes to the trash in the same undo step as the move
+    let batch_id = batch_id.unwrap_or_else(journal::new_transaction_id);
+    journal::record(trashed, Some(batch_id.clone()));
+    journal::record(
+        journal::Operation::Move { from: source, to: destination.clone(), created_dirs: Vec::new() },
+        Some(batch_id),
+    );
+    match leftovers {
+        Some(error) => Err(error),
+        None => Ok(Some(destination)),
+    }
+}
+
+// Lists every item of a batch that would collide in `destination_folder`
+#[tauri::command]
+fn check_conflicts(sources: Vec<String>, destination_folder: String) -> Result<Vec<conflicts::ConflictInfo>, String> {
+    basic_path_check(&destination_folder)?;
+    for source in &sources {
+        basic_path_check(source)?;
+    }
+    Ok(conflicts::check(&sources, Path::new(&destination_folder)))
 }
 
 #[tauri::command]
-fn create_folder(path: String) -> Result<(), String> {
+fn create_folder(path: String, batch_id: Option<String>) -> Result<(), String> {
     // Basic validation - only blocks obvious attacks
     basic_path_check(&path)?;
 
@@ -170,19 +329,106 @@ fn create_folder(path: String) -> Result<(), String> {
         basic_folder_name_check(&folder_name.to_string_lossy())?;
     }
 
-    fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    let created_dirs = create_dirs_tracked(path_obj)?;
+
+    // Nothing to undo if the folder was already there
+    if !created_dirs.is_empty() {
+        journal::record(journal::Operation::CreateFolder { path, created_dirs }, batch_id);
+    }
     Ok(())
 }
 
+// Returns the trash id, to pass on to add_to_history so the trash is only searched once
 #[tauri::command]
-fn delete_file(path: String) -> Result<(), String> {
+fn delete_file(path: String, batch_id: Option<String>) -> Result<Option<String>, String> {
     // Basic validation - only blocks obvious attacks
     basic_path_check(&path)?;
 
     trash::delete(&path).map_err(|e| e.to_string())?;
+
+    let trash_id = trash_bin::find_trash_id(Path::new(&path));
+    journal::record(journal::Operation::Delete { path, trash_id: trash_id.clone() }, batch_id);
+    Ok(trash_id)
+}
+
+// Streams the copy in the background and reports through "transfer-progress" events.
+// mode is "copy" or "move". Returns the job id, or None if the conflict policy skipped it.
+#[tauri::command]
+fn start_transfer(app: AppHandle, source: String, destination: String, mode: String, batch_id: Option<String>, conflict: Option<conflicts::ConflictPolicy>) -> Result<Option<String>, String> {
+    basic_path_check(&source)?;
+    basic_path_check(&destination)?;
+
+    let is_move = match mode.as_str() {
+        "move" => true,
+        "copy" => false,
+        _ => return Err(format!("Unknown transfer mode: {}", mode)),
+    };
+
+    let (destination, replace) = match resolve_destination(&source, &destination, conflict.unwrap_or_default())? {
+        Some(resolved) => resolved,
+        None => return Ok(None),
+    };
+    transfer::start(app, source, destination, is_move, replace, batch_id).map(Some)
+}
+
+// Moves to another drive go through start_transfer, so the frontend can show progress
+#[tauri::command]
+fn needs_transfer(source: String, destination: String) -> Result<bool, String> {
+    basic_path_check(&source)?;
+    basic_path_check(&destination)?;
+    Ok(transfer::crosses_drives(Path::new(&source), Path::new(&destination)))
+}
+
+#[tauri::command]
+fn cancel_job(jobs: State<'_, jobs::JobRegistry>, job_id: String) -> Result<(), String> {
+    jobs.cancel(&job_id)
+}
+
+#[tauri::command]
+fn pause_job(jobs: State<'_, jobs::JobRegistry>, job_id: String) -> Result<(), String> {
+    jobs.set_paused(&job_id, true)
+}
+
+#[tauri::command]
+fn resume_job(jobs: State<'_, jobs::JobRegistry>, job_id: String) -> Result<(), String> {
+    jobs.set_paused(&job_id, false)
+}
+
+#[tauri::command]
+fn list_trash() -> Result<Vec<trash_bin::TrashEntry>, String> {
+    let mut entries = trash_bin::list()?;
+    let deleted_at = history_db::deleted_timestamps()?;
+
+    for entry in entries.iter_mut() {
+        entry.history_timestamp = deleted_at.get(&entry.id).cloned();
+    }
+    Ok(entries)
+}
+
+#[tauri::command]
+fn restore_from_trash(ids: Vec<String>) -> Result<(), String> {
+    let restored = trash_bin::restore_many(&ids)?;
+    for entry in restored {
+        record_history(entry.name, entry.original_path, entry.size.unwrap_or(0), "restored".to_string(), None, None, None)?;
+    }
     Ok(())
 }
 
+#[tauri::command]
+fn purge_trash(ids: Vec<String>) -> Result<(), String> {
+    trash_bin::purge(&ids)
+}
+
+#[tauri::command]
+fn undo_last() -> Result<Option<journal::Transaction>, String> {
+    journal::undo_last()
+}
+
+#[tauri::command]
+fn redo() -> Result<Option<journal::Transaction>, String> {
+    journal::redo()
+}
+
 #[tauri::command]
 fn get_downloads_path() -> String {
     dirs::download_dir()
@@ -191,14 +437,32 @@ fn get_downloads_path() -> String {
 }
 
 #[tauri::command]
-fn get_download_history() -> Vec<DownloadHistoryEntry> {
-    load_app_data().download_history
+fn get_download_history() -> Result<Vec<DownloadHistoryEntry>, String> {
+    history_db::all()
 }
 
 #[tauri::command]
-fn add_to_history(name: String, original_path: String, size: u64, action: String, destination: Option<String>) {
-    let mut data = load_app_data();
-    
+fn query_history(query: history_db::HistoryQuery) -> Result<history_db::HistoryPage, String> {
+    history_db::query(&query)
+}
+
+#[tauri::command]
+fn history_stats(from: Option<String>, to: Option<String>, top: Option<usize>) -> Result<history_db::HistoryStats, String> {
+    history_db::stats(from.as_deref(), to.as_deref(), top.unwrap_or(10))
+}
+
+#[tauri::command]
+fn add_to_history(name: String, original_path: String, size: u64, action: String, destination: Option<String>, detected_at: Option<String>, trash_id: Option<String>) -> Result<(), String> {
+    record_history(name, original_path, size, action, destination, detected_at, trash_id)
+}
+
+// Shared by the frontend command and the auto-organize rules so both land in the same history.
+// `trash_id` is what delete_file returned for a "deleted" row, so it can be restored with one click.
+fn record_history(name: String, original_path: String, size: u64, action: String, destination: Option<String>, detected_at: Option<String>, trash_id: Option<String>) -> Result<(), String> {
+    let settings = load_app_data()?.settings;
+
+    let content_hash = redownload::arrival_hash(&action, &name, &original_path, destination.as_deref());
+
     let entry = DownloadHistoryEntry {
         name,
         original_path,
@@ -206,22 +470,226 @@ fn add_to_history(name: String, original_path: String, size: u64, action: String
         timestamp: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
         action,
         destination,
+        trash_id,
+        detected_at,
+        content_hash,
     };
-    
-    // Add to front
-    data.download_history.insert(0, entry);
-    
-    // Keep only last 50
-    data.download_history.truncate(50);
-    
-    save_app_data(&data);
+
+    // Trims to the configured limits, archiving what rolls off when enabled
+    let id = history_db::insert(&entry, &settings.history)?;
+
+    // The row goes in right away; hashing a big file would hold up the command
+    if entry.content_hash.is_none() {
+        std::thread::spawn(move || {
+            let hash = redownload::hash_organized(&entry.action, &entry.name, entry.destination.as_deref(), entry.size);
+            if let Some(hash) = hash {
+                if let Err(e) = history_db::set_content_hash(id, &hash) {
+                    eprintln!("Could not store hash for {}: {}", entry.name, e);
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+// Downloads waiting for a decision, oldest first. Snoozed ones come back when they're due.
+#[tauri::command]
+fn get_pending() -> Result<Vec<pending::PendingDownload>, String> {
+    pending::list()
+}
+
+// Takes a download off the queue. Moves, renames and deletes are recorded in history by
+// the commands that did them; "kept" has nothing else to record it, so it's done here.
+#[tauri::command]
+fn resolve_pending(app: AppHandle, id: i64, action: String) -> Result<(), String> {
+    if !matches!(action.as_str(), "moved" | "renamed" | "deleted" | "kept") {
+        return Err(format!("Unknown action {}", action));
+    }
+    let item = pending::take(id)?;
+    if action == "kept" {
+        let file = item.file;
+        record_history(file.name, file.path, file.size, action, None, Some(file.detected_at), None)?;
+    }
+    pending::notify(&app, false);
+    Ok(())
+}
+
+// Hides a download from the queue until `until` ("YYYY-MM-DD HH:MM:SS", local time)
+#[tauri::command]
+fn snooze_pending(app: AppHandle, id: i64, until: String) -> Result<(), String> {
+    pending::snooze(id, &until)?;
+    pending::notify(&app, false);
+    Ok(())
+}
+
+#[tauri::command]
+fn get_settings() -> Result<settings::Settings, String> {
+    Ok(load_app_data()?.settings)
+}
+
+// New limits apply right away to the existing history and recent destinations
+#[tauri::command]
+fn save_settings(watcher: State<'_, watcher::FolderWatcher>, settings: settings::Settings) -> Result<(), String> {
+    let mut data = load_app_data()?;
+    data.settings = settings;
+    data.recent_destinations.truncate(data.settings.recent_destinations_limit);
+    save_app_data(&data)?;
+    history_db::apply_retention(&data.settings.history)?;
+    // Added and removed folders are picked up without a restart
+    watcher.apply(&data.settings)
+}
+
+#[tauri::command]
+fn list_history_archives() -> Vec<String> {
+    history_archive::list_months()
+}
+
+#[tauri::command]
+fn query_history_archive(month: Option<String>, action: Option<String>, search: Option<String>) -> Result<Vec<DownloadHistoryEntry>, String> {
+    history_archive::query(month.as_deref(), action.as_deref(), search.as_deref())
+}
+
+#[tauri::command]
+fn export_app_data(path: String) -> Result<(), String> {
+    basic_path_check(&path)?;
+    backup::export_json(Path::new(&path))
+}
+
+#[tauri::command]
+fn export_history_csv(path: String) -> Result<(), String> {
+    basic_path_check(&path)?;
+    backup::export_history_csv(Path::new(&path))
+}
+
+#[tauri::command]
+fn import_app_data(watcher: State<'_, watcher::FolderWatcher>, path: String, mode: Option<backup::ImportMode>) -> Result<backup::ImportSummary, String> {
+    basic_path_check(&path)?;
+    let summary = backup::import_json(Path::new(&path), mode.unwrap_or_default())?;
+    // A replace brings its own watched folders and stability window, same as save_settings
+    watcher.apply(&load_app_data()?.settings)?;
+    Ok(summary)
+}
+
+#[tauri::command]
+fn search_files(indexer: State<'_, file_index::FileIndexer>, query: String, mode: Option<file_index::SearchMode>, limit: Option<usize>) -> Result<Vec<file_index::SearchResult>, String> {
+    indexer.search(&query, mode.unwrap_or_default(), limit)
+}
+
+// Cancel with cancel_job using the returned id
+#[tauri::command]
+fn search_contents(app: AppHandle, root: String, query: content_search::ContentQuery) -> Result<String, String> {
+    basic_path_check(&root)?;
+    content_search::start(app, root, query)
+}
+
+// Cancel with cancel_job; fetch results with get_disk_usage once it completes
+#[tauri::command]
+fn scan_disk_usage(app: AppHandle, root: String) -> Result<String, String> {
+    basic_path_check(&root)?;
+    disk_usage::start(app, root)
+}
+
+// Drills into the last scan covering `path` without touching the disk
+#[tauri::command]
+fn get_disk_usage(cache: State<'_, disk_usage::DiskUsageCache>, path: String, depth: Option<usize>, max_children: Option<usize>) -> Result<disk_usage::DiskUsage, String> {
+    basic_path_check(&path)?;
+    cache.get(Path::new(&path), depth, max_children)
+}
+
+// Results arrive with the "completed" duplicates-progress event; cancel with cancel_job
+#[tauri::command]
+fn find_duplicates(app: AppHandle, roots: Vec<String>, min_size: Option<u64>) -> Result<String, String> {
+    for root in &roots {
+        basic_path_check(root)?;
+    }
+    duplicates::start(app, roots, min_size.unwrap_or(1))
+}
+
+// Keeps `keep` and trashes or hardlinks the other copies, undone together as one batch
+#[tauri::command]
+fn resolve_duplicates(keep: String, duplicates: Vec<String>, mode: Option<duplicates::ResolveMode>, batch_id: Option<String>) -> Result<duplicates::ResolveSummary, String> {
+    basic_path_check(&keep)?;
+    for duplicate in &duplicates {
+        basic_path_check(duplicate)?;
+    }
+
+    let batch_id = batch_id.unwrap_or_else(journal::new_transaction_id);
+    let record_link = |operation| journal::record(operation, Some(batch_id.clone()));
+    duplicates::resolve(&keep, &duplicates, mode.unwrap_or_default(), Some(batch_id.clone()), &record_link)
+}
+
+#[tauri::command]
+fn get_index_status(indexer: State<'_, file_index::FileIndexer>) -> file_index::IndexStatus {
+    indexer.status()
+}
+
+// Roots are whole drives (from get_drives) or any folders the user picks
+#[tauri::command]
+fn set_index_roots(app: AppHandle, indexer: State<'_, file_index::FileIndexer>, roots: Vec<String>) -> Result<(), String> {
+    for root in &roots {
+        basic_path_check(root)?;
+    }
+    indexer.set_roots(app, roots)
+}
+
+#[tauri::command]
+fn rebuild_index(app: AppHandle, indexer: State<'_, file_index::FileIndexer>) -> bool {
+    indexer.rebuild(app)
+}
+
+#[tauri::command]
+fn get_rules() -> Result<Vec<rules::Rule>, String> {
+    Ok(load_app_data()?.rules)
 }
 
 #[tauri::command]
-fn clear_history() {
-    let mut data = load_app_data();
-    data.download_history.clear();
-    save_app_data(&data);
+fn save_rules(rules: Vec<rules::Rule>) -> Result<(), String> {
+    let mut data = load_app_data()?;
+    data.rules = rules;
+    save_app_data(&data)
+}
+
+// Runs rules against the current Downloads listing without touching the disk.
+// Uses the saved rules unless a draft set is passed in.
+#[tauri::command]
+fn preview_rules(rules: Option<Vec<rules::Rule>>) -> Result<rules::RulePreview, String> {
+    let data = load_app_data()?;
+    let rule_list = rules.unwrap_or(data.rules);
+    let folders = data.settings.watched_folders;
+
+    // Everything in the watched folders, once even where they nest. Hidden folders
+    // below the top level aren't gone into.
+    let mut listed = std::collections::HashSet::new();
+    let mut entries = Vec::new();
+    for folder in &folders {
+        let mut walker = walkdir::WalkDir::new(&folder.path).min_depth(1);
+        if !folder.recursive {
+            walker = walker.max_depth(1);
+        }
+        let found = walker
+            .into_iter()
+            .filter_entry(|e| e.depth() == 1 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|e| e.ok());
+        for found in found {
+            if !listed.insert(found.path().to_path_buf()) {
+                continue;
+            }
+            let Ok(metadata) = found.metadata() else { continue };
+            entries.push(FileEntry {
+                name: found.file_name().to_string_lossy().to_string(),
+                path: found.path().to_string_lossy().to_string(),
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                details: None,
+            });
+        }
+    }
+    Ok(rules::preview(&rule_list, &folders, &entries))
+}
+
+#[tauri::command]
+fn clear_history() -> Result<(), String> {
+    history_db::clear()
 }
 
 #[tauri::command]
@@ -241,6 +709,7 @@ fn get_autostart_enabled() -> bool {
 }
 
 #[tauri::command]
+#[cfg_attr(not(target_os = "windows"), allow(unused_variables))]
 fn set_autostart_enabled(enabled: bool) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
@@ -273,106 +742,54 @@ fn set_autostart_enabled(enabled: bool) -> Result<(), String> {
     }
 }
 
-fn start_watcher(app_handle: AppHandle) {
-    std::thread::spawn(move || {
-        let (tx, rx) = channel();
+// Returns what a rule did with the file, if one took care of it
+// `rule_ids` narrows the rules to the watched folder's own set (None means all of them)
+fn auto_organize(app_handle: &AppHandle, path: &Path, name: &str, size: u64, detected_at: &str, rule_ids: Option<&[String]>) -> Option<rules::PlannedAction> {
+    let rule_list = match load_app_data() {
+        Ok(data) => rules::for_folder(&data.rules, rule_ids),
+        Err(e) => {
+            eprintln!("Could not load rules: {}", e);
+            return None;
+        }
+    };
+    let rule = rules::find_matching_rule(&rule_list, path, name, size)?;
 
-        let mut watcher = match notify::recommended_watcher(move |res: Result<Event, _>| {
-            if let Ok(event) = res {
-                let _ = tx.send(event);
-            }
-        }) {
-            Ok(w) => w,
-            Err(e) => {
-                eprintln!("Failed to create file watcher: {}", e);
-                return;
-            }
-        };
+    let planned = rules::plan(rule, path, name);
+    let trash_id = match rules::apply(&planned) {
+        Ok(trash_id) => trash_id,
+        Err(e) => {
+            eprintln!("Rule \"{}\" failed for {}: {}", rule.name, name, e);
+            return None;
+        }
+    };
 
-        let downloads = match dirs::download_dir() {
-            Some(path) => path,
-            None => {
-                eprintln!("Could not find Downloads folder");
-                return;
-            }
-        };
+    println!("Rule \"{}\" {} {}", rule.name, planned.action, name);
 
-        if let Err(e) = watcher.watch(&downloads, RecursiveMode::NonRecursive) {
-            eprintln!("Failed to watch Downloads folder: {}", e);
-            return;
-        }
-        
-        println!("Watching: {:?}", downloads);
-        
-        for event in rx {
-            println!("Event detected: {:?}", event.kind);
-            
-            // Watch for Create OR Rename (browsers rename .crdownload to final name)
-            let is_relevant = matches!(
-                event.kind,
-                EventKind::Create(_) | EventKind::Modify(notify::event::ModifyKind::Name(_))
-            );
-            
-            if !is_relevant {
-                continue;
-            }
-            
-            for path in event.paths {
-                let name = path.file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default();
-                
-                println!("File: {}", name);
-                
-                // Skip temp files
-                if name.ends_with(".crdownload") || 
-                   name.ends_with(".tmp") || 
-                   name.ends_with(".partial") ||
-                   name.starts_with(".") ||
-                   name.ends_with(".download") {
-                    println!("Skipping temp file");
-                    continue;
-                }
-                
-                // Make sure file exists and is a file (not directory)
-                if !path.is_file() {
-                    println!("Not a file, skipping");
-                    continue;
-                }
-                
-                // Wait a moment for file to finish writing
-                std::thread::sleep(std::time::Duration::from_millis(500));
-                
-                if let Ok(metadata) = fs::metadata(&path) {
-                    println!("New download detected: {} ({} bytes)", name, metadata.len());
-                    
-                    let event = NewFileEvent {
-                        name,
-                        path: path.to_string_lossy().to_string(),
-                        size: metadata.len(),
-                    };
-                    
-                    // Show window when new download detected
-                    if let Some(window) = app_handle.get_webview_window("main") {
-                        let _ = window.unminimize();
-                        let _ = window.show();
-                        let _ = window.set_focus();
-                        
-                        #[cfg(target_os = "windows")]
-                        {
-                            let _ = window.set_always_on_top(true);
-                            let _ = window.set_always_on_top(false);
-                        }
-                        println!("Window shown!");
-                    }
-                    
-                    let _ = app_handle.emit("new-download", event);
-                }
-            }
-        }
-    });
+    if let Err(e) = record_history(
+        name.to_string(),
+        planned.source.clone(),
+        size,
+        planned.action.clone(),
+        planned.destination.clone(),
+        Some(detected_at.to_string()),
+        trash_id,
+    ) {
+        eprintln!("Failed to record history for {}: {}", name, e);
+    }
+
+    let _ = app_handle.emit("auto-organized", &planned);
+    Some(planned)
 }
 
+// Why data.json couldn't be loaded when the app started. It runs on default settings
+// then, and the frontend asks for this once it's up so the user knows.
+#[derive(Default)]
+struct StartupError(std::sync::Mutex<Option<String>>);
+
+#[tauri::command]
+fn get_startup_error(startup_error: State<'_, StartupError>) -> Option<String> {
+    startup_error.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
 
 fn setup_tray(app: &tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     let show = MenuItem::with_id(app, "show", "Show FileForge", true, None::<&str>)?;
@@ -425,10 +842,18 @@ pub fn run() {
     
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .manage(jobs::JobRegistry::default())
+        .manage(file_index::FileIndexer::default())
+        .manage(disk_usage::DiskUsageCache::default())
+        .manage(watcher::FolderWatcher::default())
+        .manage(pending::PendingInbox::default())
+        .manage(StartupError::default())
         .invoke_handler(tauri::generate_handler![
             get_drives, 
             list_directory, 
+            list_directory_ex,
             move_file, 
+            check_conflicts,
             create_folder,
             get_downloads_path,
             delete_file,
@@ -437,12 +862,59 @@ pub fn run() {
             get_recent_destinations,
             add_recent_destination,
             get_download_history,
+            query_history,
+            history_stats,
             add_to_history,
-            clear_history
+            clear_history,
+            get_rules,
+            save_rules,
+            get_settings,
+            save_settings,
+            list_history_archives,
+            query_history_archive,
+            export_app_data,
+            export_history_csv,
+            import_app_data,
+            preview_rules,
+            undo_last,
+            redo,
+            list_trash,
+            restore_from_trash,
+            purge_trash,
+            start_transfer,
+            needs_transfer,
+            cancel_job,
+            pause_job,
+            resume_job,
+            search_files,
+            search_contents,
+            scan_disk_usage,
+            get_disk_usage,
+            find_duplicates,
+            resolve_duplicates,
+            get_index_status,
+            set_index_roots,
+            rebuild_index,
+            get_pending,
+            resolve_pending,
+            snooze_pending,
+            get_startup_error
         ])
         .setup(move |app| {
             setup_tray(app)?;
-            start_watcher(app.handle().clone());
+            let settings = match load_app_data() {
+                Ok(data) => data.settings,
+                Err(e) => {
+                    eprintln!("Could not load app data, running on default settings: {}", e);
+                    *app.state::<StartupError>().0.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+                    settings::Settings::default()
+                }
+            };
+            if let Err(e) = app.state::<watcher::FolderWatcher>().start(app.handle().clone(), &settings) {
+                eprintln!("{}", e);
+            }
+            app.state::<file_index::FileIndexer>().start(app.handle().clone());
+            app.state::<pending::PendingInbox>().start(app.handle().clone());
             
             // Hide window if started with --hidden flag
             if start_hidden {
@@ -464,64 +936,87 @@ pub fn run() {
             eprintln!("Error running Tauri application: {}", e);
         });
 }
-use std::io::{Read, Write};
-
 #[derive(Serialize, serde::Deserialize, Clone)]
 struct DownloadHistoryEntry {
     name: String,
     original_path: String,
     size: u64,
     timestamp: String,
-    action: String,  // "moved", "deleted", "kept"
+    action: String,  // "moved", "renamed", "deleted", "kept", "restored"
     destination: Option<String>,
+    #[serde(default)]
+    trash_id: Option<String>,
+    // When the file showed up in Downloads, if the watcher saw it arrive
+    #[serde(default)]
+    detected_at: Option<String>,
+    // SHA-256 of moved and renamed files, for spotting re-downloads
+    #[serde(default)]
+    content_hash: Option<String>,
 }
 
-#[derive(Serialize, serde::Deserialize, Clone)]
+#[derive(Serialize, serde::Deserialize, Clone, Default)]
 struct AppData {
+    // Older files are upgraded by migrations::migrate on load
+    #[serde(default)]
+    schema_version: u64,
     recent_destinations: Vec<String>,
+    // Only read once, to import into history.db - new entries go straight to the database
     download_history: Vec<DownloadHistoryEntry>,
+    #[serde(default)]
+    rules: Vec<rules::Rule>,
+    #[serde(default)]
+    settings: settings::Settings,
 }
 
-fn get_app_data_path() -> std::path::PathBuf {
+fn get_app_dir() -> std::path::PathBuf {
     let mut path = dirs::config_dir().unwrap_or_else(|| std::path::PathBuf::from("."));
     path.push("FileForge");
     fs::create_dir_all(&path).ok();
-    path.push("data.json");
     path
 }
 
-fn load_app_data() -> AppData {
+fn get_app_data_path() -> std::path::PathBuf {
+    get_app_dir().join("data.json")
+}
+
+// Missing file means first run. A corrupt one is recovered from data.json.bak,
+// and only if that fails too does the error reach the caller.
+fn load_app_data() -> Result<AppData, String> {
     let path = get_app_data_path();
-    if let Ok(mut file) = fs::File::open(&path) {
-        let mut contents = String::new();
-        if file.read_to_string(&mut contents).is_ok() {
-            if let Ok(data) = serde_json::from_str(&contents) {
-                return data;
-            }
+    let mut value: serde_json::Value = match storage::read_json(&path)? {
+        Some(value) => value,
+        None => {
+            return Ok(AppData {
+                schema_version: migrations::CURRENT_VERSION,
+                ..Default::default()
+            })
         }
+    };
+
+    let upgraded = migrations::migrate(&mut value)?;
+    let data: AppData = serde_json::from_value(value)
+        .map_err(|e| format!("{} is not valid app data: {}", path.display(), e))?;
+
+    // Save right away so the pre-upgrade file is what ends up in data.json.bak
+    if upgraded {
+        save_app_data(&data)?;
     }
-    AppData {
-        recent_destinations: Vec::new(),
-        download_history: Vec::new(),
-    }
+    Ok(data)
 }
 
-fn save_app_data(data: &AppData) {
-    let path = get_app_data_path();
-    if let Ok(mut file) = fs::File::create(&path) {
-        let json = serde_json::to_string_pretty(data).unwrap_or_default();
-        let _ = file.write_all(json.as_bytes());
-    }
+fn save_app_data(data: &AppData) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(data).map_err(|e| e.to_string())?;
+    storage::write_atomic(&get_app_data_path(), json.as_bytes())
 }
 
 #[tauri::command]
-fn get_recent_destinations() -> Vec<String> {
-    load_app_data().recent_destinations
+fn get_recent_destinations() -> Result<Vec<String>, String> {
+    Ok(load_app_data()?.recent_destinations)
 }
 
 #[tauri::command]
-fn add_recent_destination(path: String) {
-    let mut data = load_app_data();
+fn add_recent_destination(path: String) -> Result<(), String> {
+    let mut data = load_app_data()?;
     
     // Remove if already exists (we'll add it to front)
     data.recent_destinations.retain(|p| p != &path);
@@ -529,8 +1024,8 @@ fn add_recent_destination(path: String) {
     // Add to front
     data.recent_destinations.insert(0, path);
     
-    // Keep only last 5
-    data.recent_destinations.truncate(5);
+    let limit = data.settings.recent_destinations_limit;
+    data.recent_destinations.truncate(limit);
     
-    save_app_data(&data);
+    save_app_data(&data)
 }
\ No newline at end of file
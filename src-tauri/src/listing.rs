@@ -0,0 +1,357 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::file_details::{self, FileDetails};
+
+const DEFAULT_PAGE_SIZE: usize = 500;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ListSort {
+    // Natural order, so "file2" comes before "file10"
+    #[default]
+    Name,
+    Size,
+    Modified,
+    // By extension, then name
+    Type,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ListOptions {
+    // 1 lists just this folder, 2 adds its subfolders' contents, and so on. 0 means no limit.
+    pub depth: usize,
+    // Glob patterns on the file name. Include filters files only; exclude also prunes folders.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub show_hidden: bool,
+    pub sort: ListSort,
+    pub descending: bool,
+    // Folders stay on top whatever the sort direction
+    pub folders_first: bool,
+    // Timestamps, permissions, MIME type etc. for every entry on the page (see file_details)
+    pub details: bool,
+    // next_cursor from the previous page
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+impl Default for ListOptions {
+    fn default() -> Self {
+        ListOptions {
+            depth: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            show_hidden: false,
+            sort: ListSort::Name,
+            descending: false,
+            folders_first: true,
+            details: false,
+            cursor: None,
+            limit: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ListEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    // Seconds since the Unix epoch
+    pub modified: Option<u64>,
+    // 1 for direct children of the listed folder
+    pub depth: usize,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub details: Option<FileDetails>,
+}
+
+#[derive(Serialize)]
+pub struct DirectoryPage {
+    pub entries: Vec<ListEntry>,
+    // Matching entries across all pages
+    pub total: usize,
+    // None on the last page
+    pub next_cursor: Option<String>,
+}
+
+// Compares runs of digits by value and everything else case-insensitively
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+                        digits.push(c);
+                        chars.next();
+                    }
+                    digits
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let (x_trimmed, y_trimmed) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    // "01" after "1" so equal values still have a stable order
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn extension_of(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+// A total order (ties end on the path) so a cursor always points at one spot
+fn compare(a: &ListEntry, b: &ListEntry, options: &ListOptions) -> Ordering {
+    if options.folders_first && a.is_dir != b.is_dir {
+        return b.is_dir.cmp(&a.is_dir);
+    }
+    let ordering = match options.sort {
+        ListSort::Name => natural_cmp(&a.name, &b.name),
+        ListSort::Size => a.size.cmp(&b.size),
+        ListSort::Modified => a.modified.cmp(&b.modified),
+        ListSort::Type => extension_of(&a.name).cmp(&extension_of(&b.name)),
+    }
+    .then_with(|| natural_cmp(&a.name, &b.name))
+    .then_with(|| a.path.cmp(&b.path));
+
+    if options.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if let Ok(metadata) = entry.metadata() {
+            return metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0;
+        }
+    }
+    false
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<glob::Pattern>, String> {
+    patterns
+        .iter()
+        .map(|p| glob::Pattern::new(p).map_err(|e| format!("Invalid pattern {}: {}", p, e)))
+        .collect()
+}
+
+pub fn list(path: &Path, options: &ListOptions) -> Result<DirectoryPage, String> {
+    if !path.is_dir() {
+        return Err(format!("{} is not a folder", path.display()));
+    }
+    let include = compile_patterns(&options.include)?;
+    let exclude = compile_patterns(&options.exclude)?;
+    let match_options = glob::MatchOptions {
+        case_sensitive: false,
+        ..Default::default()
+    };
+    let cursor: Option<ListEntry> = match &options.cursor {
+        Some(cursor) => Some(serde_json::from_str(cursor).map_err(|_| "Invalid cursor".to_string())?),
+        None => None,
+    };
+
+    let mut walker = WalkDir::new(path).min_depth(1);
+    if options.depth > 0 {
+        walker = walker.max_depth(options.depth);
+    }
+
+    // Each entry keeps its own (not followed) metadata, for details on the rows that make the page
+    let mut entries: Vec<(ListEntry, Metadata)> = walker
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            (options.show_hidden || !is_hidden(entry))
+                && !exclude.iter().any(|p| p.matches_with(&name, match_options))
+        })
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // One symlink_metadata per entry; only links cost a second call to follow them
+            let own = entry.metadata().ok()?;
+            let metadata = if own.file_type().is_symlink() {
+                fs::metadata(entry.path()).ok()?
+            } else {
+                own.clone()
+            };
+            if !metadata.is_dir() && !include.is_empty() && !include.iter().any(|p| p.matches_with(&name, match_options)) {
+                return None;
+            }
+            let listed = ListEntry {
+                path: entry.path().to_string_lossy().to_string(),
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+                depth: entry.depth(),
+                details: None,
+                name,
+            };
+            Some((listed, own))
+        })
+        .collect();
+
+    let total = entries.len();
+    entries.sort_by(|a, b| compare(&a.0, &b.0, options));
+
+    let start = match &cursor {
+        Some(cursor) => entries.partition_point(|(e, _)| compare(e, cursor, options) != Ordering::Greater),
+        None => 0,
+    };
+    let limit = options.limit.max(1);
+    let mut page: Vec<(ListEntry, Metadata)> = entries.drain(start..).take(limit + 1).collect();
+
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        // The cursor only needs the sort fields
+        page.last().and_then(|(last, _)| serde_json::to_string(last).ok())
+    } else {
+        None
+    };
+
+    Ok(DirectoryPage {
+        entries: page
+            .into_iter()
+            .map(|(mut entry, own)| {
+                if options.details {
+                    entry.details = Some(file_details::gather(Path::new(&entry.path), &own));
+                }
+                entry
+            })
+            .collect(),
+        total,
+        next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec!["file10.txt", "File2.txt", "file1.txt", "file02.txt", "a"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["a", "file1.txt", "File2.txt", "file02.txt", "file10.txt"]);
+    }
+
+    #[test]
+    fn pages_through_filtered_tree() {
+        let root = std::env::temp_dir().join(format!("fileforge-listing-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub/node_modules")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        for name in ["img10.png", "img2.png", "img1.png", "notes.txt", "sub/img3.png", "sub/node_modules/x.png", ".git/config"] {
+            fs::write(root.join(name), b"x").unwrap();
+        }
+
+        let mut options = ListOptions {
+            depth: 0,
+            include: vec!["*.PNG".to_string()],
+            exclude: vec!["node_modules".to_string()],
+            limit: 2,
+            ..Default::default()
+        };
+
+        let mut seen = Vec::new();
+        loop {
+            let page = list(&root, &options).unwrap();
+            assert_eq!(page.total, 5);
+            seen.extend(page.entries.iter().map(|e| e.name.clone()));
+            match page.next_cursor {
+                Some(cursor) => options.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, ["sub", "img1.png", "img2.png", "img3.png", "img10.png"]);
+
+        options.cursor = None;
+        options.show_hidden = true;
+        options.include.clear();
+        options.depth = 1;
+        options.limit = 100;
+        let names: Vec<String> = list(&root, &options).unwrap().entries.into_iter().map(|e| e.name).collect();
+        assert_eq!(names, [".git", "sub", "img1.png", "img2.png", "img10.png", "notes.txt"]);
+
+        options.details = true;
+        options.limit = 2;
+        let page = list(&root, &options).unwrap();
+        let kinds: Vec<&str> = page.entries.iter().filter_map(|e| e.details.as_ref()).map(|d| d.kind.as_str()).collect();
+        assert_eq!(kinds, ["dir", "dir"]);
+        // Details aren't part of the cursor
+        assert!(!page.next_cursor.unwrap().contains("details"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_are_listed_as_their_target() {
+        let root = std::env::temp_dir().join(format!("fileforge-listing-links-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("folder")).unwrap();
+        fs::write(root.join("file.txt"), b"hello").unwrap();
+        std::os::unix::fs::symlink(root.join("file.txt"), root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(root.join("folder"), root.join("link-folder")).unwrap();
+
+        let options = ListOptions {
+            details: true,
+            ..Default::default()
+        };
+        let entries = list(&root, &options).unwrap().entries;
+        let summary: Vec<(&str, bool, u64, &str)> = entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.is_dir, e.size, e.details.as_ref().unwrap().kind.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("folder", true, 0, "dir"),
+                ("link-folder", true, 0, "symlink"),
+                ("file.txt", false, 5, "file"),
+                ("link.txt", false, 5, "symlink"),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
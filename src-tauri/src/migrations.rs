@@ -0,0 +1,225 @@
+use serde_json::{json, Value};
+
+// Bump this and append to MIGRATIONS whenever AppData changes shape
+pub const CURRENT_VERSION: u64 = 5;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+// MIGRATIONS[n] upgrades a version-n file to version n + 1
+const MIGRATIONS: &[Migration] = &[v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+// v0 is every data.json written before schema_version existed
+fn v0_to_v1(data: &mut Value) -> Result<(), String> {
+    let root = data.as_object_mut().ok_or("data.json is not a JSON object")?;
+
+    for key in ["recent_destinations", "download_history", "rules"] {
+        root.entry(key).or_insert_with(|| json!([]));
+    }
+
+    if let Some(history) = root.get_mut("download_history").and_then(Value::as_array_mut) {
+        for entry in history.iter_mut().filter_map(Value::as_object_mut) {
+            entry.entry("trash_id").or_insert(Value::Null);
+        }
+    }
+    Ok(())
+}
+
+// Settings arrive with the limits that used to be hardcoded (50 history, 5 recent)
+fn v1_to_v2(data: &mut Value) -> Result<(), String> {
+    let root = data.as_object_mut().ok_or("data.json is not a JSON object")?;
+    root.entry("settings").or_insert_with(|| {
+        json!({
+            "history": {
+                "default": { "max_entries": 50, "max_age_days": null },
+                "per_action": {},
+                "archive": true
+            },
+            "recent_destinations_limit": 5
+        })
+    });
+    Ok(())
+}
+
+// The watcher used to be fixed on Downloads, so that becomes the only watched folder
+// (a file without settings gets them all from Settings::default)
+fn v2_to_v3(data: &mut Value) -> Result<(), String> {
+    if let Some(settings) = data.get_mut("settings").and_then(Value::as_object_mut) {
+        let folders = serde_json::to_value(crate::settings::default_watched_folders()).map_err(|e| e.to_string())?;
+        settings.entry("watched_folders").or_insert(folders);
+    }
+    Ok(())
+}
+
+// Replaces the fixed 500 ms wait after a file shows up
+fn v3_to_v4(data: &mut Value) -> Result<(), String> {
+    if let Some(settings) = data.get_mut("settings").and_then(Value::as_object_mut) {
+        settings.entry("stable_window_ms").or_insert(json!(2000));
+    }
+    Ok(())
+}
+
+// Moves used to record only the destination folder; they now record where the file
+// ended up. Same rewrite as the one history.db gets, for history that hasn't reached it
+// yet: data.json from before the database, and older exports.
+fn v4_to_v5(data: &mut Value) -> Result<(), String> {
+    let Some(history) = data.get_mut("download_history").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    for entry in history.iter_mut().filter_map(Value::as_object_mut) {
+        if entry.get("action").and_then(Value::as_str) != Some("moved") {
+            continue;
+        }
+        let (Some(name), Some(folder)) = (
+            entry.get("name").and_then(Value::as_str),
+            entry.get("destination").and_then(Value::as_str),
+        ) else {
+            continue;
+        };
+        // Already a full path
+        if folder.rsplit(['/', '\\']).next() == Some(name) {
+            continue;
+        }
+        let path = if folder.ends_with(['/', '\\']) {
+            format!("{}{}", folder, name)
+        } else if folder.contains('\\') {
+            format!("{}\\{}", folder, name)
+        } else {
+            format!("{}/{}", folder, name)
+        };
+        entry.insert("destination".to_string(), json!(path));
+    }
+    Ok(())
+}
+
+// Upgrades `data` in place. Returns true if anything changed (so the caller can save).
+pub fn migrate(data: &mut Value) -> Result<bool, String> {
+    let version = data.get("schema_version").and_then(Value::as_u64).unwrap_or(0);
+
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "data.json uses schema version {}, but this version of FileForge only understands up to {}",
+            version, CURRENT_VERSION
+        ));
+    }
+
+    for from in version..CURRENT_VERSION {
+        MIGRATIONS[from as usize](data)?;
+        data["schema_version"] = json!(from + 1);
+    }
+    Ok(version < CURRENT_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppData;
+
+    fn load_fixture(contents: &str) -> (AppData, bool) {
+        let mut value: Value = serde_json::from_str(contents).unwrap();
+        let changed = migrate(&mut value).unwrap();
+        (serde_json::from_value(value).unwrap(), changed)
+    }
+
+    #[test]
+    fn migrations_cover_every_version() {
+        assert_eq!(MIGRATIONS.len() as u64, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn loads_v0_file() {
+        let (data, changed) = load_fixture(include_str!("../tests/fixtures/app_data_v0.json"));
+
+        assert!(changed);
+        assert_eq!(data.schema_version, CURRENT_VERSION);
+        assert_eq!(data.recent_destinations.len(), 2);
+        assert_eq!(data.download_history.len(), 2);
+        assert_eq!(data.download_history[0].name, "invoice_2026_01.pdf");
+        assert_eq!(data.download_history[0].destination.as_deref(), Some("D:\\Documents\\invoice_2026_01.pdf"));
+        assert_eq!(data.download_history[1].action, "deleted");
+        assert!(data.download_history[1].trash_id.is_none());
+        assert!(data.rules.is_empty());
+        assert_eq!(data.settings.history.default.max_entries, Some(50));
+    }
+
+    #[test]
+    fn loads_v1_file() {
+        let (data, changed) = load_fixture(include_str!("../tests/fixtures/app_data_v1.json"));
+
+        assert!(changed);
+        assert_eq!(data.schema_version, CURRENT_VERSION);
+        assert_eq!(data.download_history[0].trash_id.as_deref(), Some("C:\\$Recycle.Bin\\S-1-5-21\\$RAB12CD.pdf"));
+        assert_eq!(data.rules.len(), 1);
+        assert_eq!(data.rules[0].id, "pdfs");
+        assert_eq!(data.settings.recent_destinations_limit, 5);
+        assert!(data.settings.history.archive);
+    }
+
+    #[test]
+    fn loads_v2_file() {
+        let (data, changed) = load_fixture(include_str!("../tests/fixtures/app_data_v2.json"));
+
+        assert!(changed);
+        assert_eq!(data.schema_version, CURRENT_VERSION);
+        assert_eq!(data.settings.recent_destinations_limit, 10);
+        assert_eq!(data.settings.history.default.max_entries, None);
+        assert_eq!(data.settings.history.default.max_age_days, Some(90));
+        assert_eq!(data.settings.history.per_action["kept"].max_entries, Some(20));
+        assert!(data.settings.watched_folders == crate::settings::default_watched_folders());
+    }
+
+    #[test]
+    fn loads_v3_file() {
+        let (data, changed) = load_fixture(include_str!("../tests/fixtures/app_data_v3.json"));
+
+        assert!(changed);
+        assert_eq!(data.schema_version, CURRENT_VERSION);
+        let folders = &data.settings.watched_folders;
+        assert_eq!(folders.len(), 2);
+        assert!(!folders[0].recursive);
+        assert!(folders[0].rules.is_none());
+        assert!(folders[1].recursive);
+        assert_eq!(folders[1].rules.as_deref(), Some(&["scans".to_string()][..]));
+        assert_eq!(data.settings.stable_window_ms, 2000);
+    }
+
+    #[test]
+    fn loads_v4_file() {
+        let (data, changed) = load_fixture(include_str!("../tests/fixtures/app_data_v4.json"));
+
+        assert!(changed);
+        assert_eq!(data.schema_version, CURRENT_VERSION);
+        let destinations: Vec<Option<&str>> = data.download_history.iter().map(|e| e.destination.as_deref()).collect();
+        assert_eq!(
+            destinations,
+            vec![
+                Some("C:\\Users\\user\\Documents\\invoice.pdf"),
+                Some("/home/user/Docs/notes.txt"),
+                Some("/home/user/Docs/photo.jpg"),
+                Some("/home/user/Downloads/2026-01-05_scan.png"),
+            ]
+        );
+    }
+
+    #[test]
+    fn loads_v5_file() {
+        let (data, changed) = load_fixture(include_str!("../tests/fixtures/app_data_v5.json"));
+
+        assert!(!changed);
+        assert_eq!(data.schema_version, CURRENT_VERSION);
+        assert_eq!(data.settings.stable_window_ms, 5000);
+        assert_eq!(data.settings.watched_folders.len(), 1);
+    }
+
+    #[test]
+    fn fills_missing_sections() {
+        let (data, _) = load_fixture("{}");
+        assert!(data.recent_destinations.is_empty());
+        assert!(data.download_history.is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_schema() {
+        let mut value = json!({ "schema_version": CURRENT_VERSION + 1 });
+        assert!(migrate(&mut value).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::history_db::{sql_err, with_db};
+use crate::NewFileEvent;
+
+// Downloads finishing within this long of each other raise the window once
+const BATCH_DELAY: Duration = Duration::from_secs(1);
+const SNOOZE_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// A download waiting for a decision. Kept in history.db, so the queue survives restarts.
+#[derive(Serialize, Clone)]
+pub struct PendingDownload {
+    pub id: i64,
+    #[serde(flatten)]
+    pub file: NewFileEvent,
+    pub queued_at: String,
+}
+
+#[derive(Default)]
+pub struct PendingInbox {
+    notify_scheduled: AtomicBool,
+}
+
+fn now() -> String {
+    chrono::Local::now().format(TIMESTAMP_FORMAT).to_string()
+}
+
+// Snoozes are kept in UTC, which has no clock changes to wake them early or late
+fn now_utc() -> String {
+    chrono::Utc::now().format(TIMESTAMP_FORMAT).to_string()
+}
+
+fn pending_from_row(row: &Row) -> rusqlite::Result<PendingDownload> {
+    let duplicate_of: Option<String> = row.get(5)?;
+    Ok(PendingDownload {
+        id: row.get(0)?,
+        file: NewFileEvent {
+            name: row.get(1)?,
+            path: row.get(2)?,
+            size: row.get::<_, i64>(3)?.max(0) as u64,
+            detected_at: row.get(4)?,
+            duplicate_of: duplicate_of.and_then(|json| serde_json::from_str(&json).ok()),
+        },
+        queued_at: row.get(6)?,
+    })
+}
+
+// A file that is queued again (re-downloaded under the same name) moves to the back
+fn queue_with(conn: &mut Connection, files: &[NewFileEvent], queued_at: &str) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for file in files {
+        let duplicate_of = file.duplicate_of.as_ref().and_then(|d| serde_json::to_string(d).ok());
+        tx.execute(
+            "INSERT OR REPLACE INTO pending (path, name, size, detected_at, duplicate_of, queued_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![file.path, file.name, file.size as i64, file.detected_at, duplicate_of, queued_at],
+        )?;
+    }
+    tx.commit()
+}
+
+// Everything that isn't snoozed, oldest first. `now` is UTC, like snoozed_until.
+fn due_with(conn: &Connection, now: &str) -> rusqlite::Result<Vec<PendingDownload>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, name, path, size, detected_at, duplicate_of, queued_at FROM pending
+         WHERE snoozed_until IS NULL OR snoozed_until <= ?1 ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![now], pending_from_row)?;
+    rows.collect()
+}
+
+fn take_with(conn: &Connection, id: i64) -> rusqlite::Result<Option<PendingDownload>> {
+    let item = conn
+        .query_row(
+            "SELECT id, name, path, size, detected_at, duplicate_of, queued_at FROM pending WHERE id = ?1",
+            params![id],
+            pending_from_row,
+        )
+        .optional()?;
+    conn.execute("DELETE FROM pending WHERE id = ?1", params![id])?;
+    Ok(item)
+}
+
+// Snoozes that ran out since the last check. They lose the snooze, so each comes due once.
+fn wake_with(conn: &Connection, now: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE pending SET snoozed_until = NULL WHERE snoozed_until IS NOT NULL AND snoozed_until <= ?1",
+        params![now],
+    )
+}
+
+// Due downloads in the order they arrived. Files moved or deleted outside FileForge drop out.
+pub fn list() -> Result<Vec<PendingDownload>, String> {
+    with_db(|conn| {
+        let due = due_with(conn, &now_utc()).map_err(sql_err)?;
+        let (present, gone): (Vec<_>, Vec<_>) = due.into_iter().partition(|p| Path::new(&p.file.path).is_file());
+        for item in gone {
+            take_with(conn, item.id).map_err(sql_err)?;
+        }
+        Ok(present)
+    })
+}
+
+// Removes a download from the queue and returns it
+pub fn take(id: i64) -> Result<PendingDownload, String> {
+    with_db(|conn| take_with(conn, id).map_err(sql_err))?.ok_or_else(|| format!("No pending download with id {}", id))
+}
+
+// `until` is a local "YYYY-MM-DD HH:MM:SS" timestamp
+pub fn snooze(id: i64, until: &str) -> Result<(), String> {
+    let until = crate::history_db::local_instant(until)
+        .ok_or_else(|| format!("Invalid time {}", until))?
+        .with_timezone(&chrono::Utc)
+        .format(TIMESTAMP_FORMAT)
+        .to_string();
+    let updated = with_db(|conn| {
+        conn.execute("UPDATE pending SET snoozed_until = ?1 WHERE id = ?2", params![until, id])
+            .map_err(sql_err)
+    })?;
+    if updated == 0 {
+        return Err(format!("No pending download with id {}", id));
+    }
+    Ok(())
+}
+
+fn raise_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+
+        #[cfg(target_os = "windows")]
+        {
+            let _ = window.set_always_on_top(true);
+            let _ = window.set_always_on_top(false);
+        }
+        println!("Window shown!");
+    }
+}
+
+// Sends the frontend the queue as it is now, and brings the window up if there's news
+pub fn notify(app: &AppHandle, raise: bool) {
+    match list() {
+        Ok(items) => {
+            if raise && !items.is_empty() {
+                raise_window(app);
+            }
+            let _ = app.emit("pending-updated", items);
+        }
+        Err(e) => eprintln!("Could not load pending downloads: {}", e),
+    }
+}
+
+impl PendingInbox {
+    // Wakes snoozed downloads when their time comes
+    pub fn start(&self, app: AppHandle) {
+        std::thread::spawn(move || loop {
+            std::thread::sleep(SNOOZE_CHECK_INTERVAL);
+            match with_db(|conn| wake_with(conn, &now_utc()).map_err(sql_err)) {
+                Ok(0) => {}
+                Ok(_) => notify(&app, true),
+                Err(e) => eprintln!("Could not check snoozed downloads: {}", e),
+            }
+        });
+    }
+
+    // Queues a batch. The frontend hears about it (and the window comes up) a moment
+    // later, together with anything else that finished in the meantime.
+    pub fn add(&self, app: &AppHandle, batch: Vec<NewFileEvent>) {
+        if batch.is_empty() {
+            return;
+        }
+        if let Err(e) = with_db(|conn| queue_with(conn, &batch, &now()).map_err(sql_err)) {
+            eprintln!("Could not queue {} downloads: {}", batch.len(), e);
+            return;
+        }
+        if self.notify_scheduled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let app = app.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(BATCH_DELAY);
+            app.state::<PendingInbox>().notify_scheduled.store(false, Ordering::SeqCst);
+            notify(&app, true);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> NewFileEvent {
+        NewFileEvent {
+            name: path.rsplit('/').next().unwrap().to_string(),
+            path: path.to_string(),
+            size: 10,
+            detected_at: "2026-05-01 09:00:00".to_string(),
+            duplicate_of: None,
+        }
+    }
+
+    fn paths(items: &[PendingDownload]) -> Vec<&str> {
+        items.iter().map(|p| p.file.path.as_str()).collect()
+    }
+
+    #[test]
+    fn queue_snooze_and_resolve() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::history_db::init_schema(&mut conn).unwrap();
+        let noon = "2026-05-01 12:00:00";
+
+        queue_with(&mut conn, &[file("d/a.pdf"), file("d/b.zip")], "2026-05-01 10:00:00").unwrap();
+        queue_with(&mut conn, &[file("d/c.png"), file("d/a.pdf")], "2026-05-01 11:00:00").unwrap();
+        let due = due_with(&conn, noon).unwrap();
+        assert_eq!(paths(&due), ["d/b.zip", "d/c.png", "d/a.pdf"]);
+
+        conn.execute("UPDATE pending SET snoozed_until = '2026-05-01 13:00:00' WHERE path = 'd/b.zip'", [])
+            .unwrap();
+        assert_eq!(paths(&due_with(&conn, noon).unwrap()), ["d/c.png", "d/a.pdf"]);
+        assert_eq!(wake_with(&conn, noon).unwrap(), 0);
+        assert_eq!(wake_with(&conn, "2026-05-01 13:00:00").unwrap(), 1);
+        assert_eq!(due_with(&conn, noon).unwrap().len(), 3);
+
+        let taken = take_with(&conn, due[1].id).unwrap().unwrap();
+        assert_eq!(taken.file.path, "d/c.png");
+        assert!(take_with(&conn, due[1].id).unwrap().is_none());
+        assert_eq!(paths(&due_with(&conn, noon).unwrap()), ["d/b.zip", "d/a.pdf"]);
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::{duplicates, history_db};
+
+// Hashing a multi-GB download would hold up the watcher for too long
+const MAX_HASHED_SIZE: u64 = 1024 * 1024 * 1024;
+// Hashes of recent downloads, waiting for the history entry that says what happened to them
+const PENDING_LIMIT: usize = 100;
+
+static PENDING_HASHES: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+// Where an earlier copy of the same file was organized to
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DuplicateOf {
+    pub path: String,
+    pub action: String,
+    pub timestamp: String,
+}
+
+fn remember(path: &Path, hash: &str) {
+    let mut pending = PENDING_HASHES.lock().unwrap_or_else(|e| e.into_inner());
+    let path = path.to_string_lossy().to_string();
+    pending.retain(|(p, _)| *p != path);
+    pending.push((path, hash.to_string()));
+    if pending.len() > PENDING_LIMIT {
+        pending.remove(0);
+    }
+}
+
+fn take_hash(original_path: &str) -> Option<String> {
+    let mut pending = PENDING_HASHES.lock().unwrap_or_else(|e| e.into_inner());
+    let index = pending.iter().position(|(p, _)| p == original_path)?;
+    Some(pending.remove(index).1)
+}
+
+// Whether a recorded move destination ends in the item itself - `name`, or "name (n)"
+// when a conflict kept both copies - rather than only the folder it went into
+fn names_item(destination: &Path, name: &str) -> bool {
+    let Some(last) = destination.file_name().map(|n| n.to_string_lossy().to_string()) else {
+        return false;
+    };
+    if last == name {
+        return true;
+    }
+    // Files are numbered before the extension, folders at the very end
+    let mut forms = vec![(name, "")];
+    if let Some((stem, extension)) = name.rsplit_once('.').filter(|(stem, _)| !stem.is_empty()) {
+        forms.push((stem, extension));
+    }
+    forms.into_iter().any(|(stem, extension)| {
+        let numbered = last
+            .strip_prefix(stem)
+            .and_then(|rest| rest.strip_prefix(" ("))
+            .and_then(|rest| if extension.is_empty() { Some(rest) } else { rest.strip_suffix(extension)?.strip_suffix('.') })
+            .and_then(|rest| rest.strip_suffix(')'));
+        numbered.is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+// Moves and renames record where the item ended up, which isn't always `name` (a
+// conflict kept both copies). Rows from before that only had the folder for moves.
+// Told apart by the recorded path alone, as a moved folder is a folder on disk too.
+fn organized_path(action: &str, name: &str, destination: Option<&str>) -> Option<PathBuf> {
+    match (action, destination) {
+        ("moved", Some(destination)) if !names_item(Path::new(destination), name) => Some(Path::new(destination).join(name)),
+        ("moved" | "renamed", Some(destination)) => Some(PathBuf::from(destination)),
+        _ => None,
+    }
+}
+
+// The hash the watcher took when this download arrived, for its history entry
+pub fn arrival_hash(action: &str, name: &str, original_path: &str, destination: Option<&str>) -> Option<String> {
+    organized_path(action, name, destination)?;
+    take_hash(original_path)
+}
+
+// Files organized from the list weren't hashed on arrival, so they're hashed where they
+// ended up. Can take a while for big files; keep it off the main thread.
+pub fn hash_organized(action: &str, name: &str, destination: Option<&str>, size: u64) -> Option<String> {
+    let organized = organized_path(action, name, destination)?;
+    if size == 0 || size > MAX_HASHED_SIZE {
+        return None;
+    }
+    duplicates::hash_file(&organized).ok()
+}
+
+// Hashes a new download and looks for the same content among files organized before.
+// Only matches whose organized copy is still there (same size) are reported.
+pub fn check(path: &Path, size: u64) -> Option<DuplicateOf> {
+    if size == 0 || size > MAX_HASHED_SIZE {
+        return None;
+    }
+    let hash = match duplicates::hash_file(path) {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!("Could not hash {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    remember(path, &hash);
+
+    let earlier = match history_db::organized_with_hash(&hash) {
+        Ok(earlier) => earlier,
+        Err(e) => {
+            eprintln!("Could not look up earlier downloads: {}", e);
+            return None;
+        }
+    };
+
+    earlier.into_iter().find_map(|entry| {
+        let organized = organized_path(&entry.action, &entry.name, entry.destination.as_deref())?;
+        if organized == path || std::fs::metadata(&organized).map(|m| m.len()).ok() != Some(size) {
+            return None;
+        }
+        Some(DuplicateOf {
+            path: organized.to_string_lossy().to_string(),
+            action: entry.action,
+            timestamp: entry.timestamp,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn organized_path_follows_action() {
+        let dir = std::env::temp_dir().join(format!("fileforge-redownload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let folder = dir.to_string_lossy().to_string();
+        let kept_both = dir.join("a (1).zip").to_string_lossy().to_string();
+
+        assert_eq!(organized_path("moved", "a.zip", Some(&kept_both)), Some(dir.join("a (1).zip")));
+        // Older rows recorded just the folder
+        assert_eq!(organized_path("moved", "a.zip", Some(&folder)), Some(dir.join("a.zip")));
+        assert_eq!(organized_path("renamed", "a.zip", Some("b.zip")), Some(PathBuf::from("b.zip")));
+        // A moved folder is recorded as the folder itself, which is there on disk
+        let photos = dir.join("Photos");
+        std::fs::create_dir_all(&photos).unwrap();
+        assert_eq!(organized_path("moved", "Photos", Some(&photos.to_string_lossy())), Some(photos.clone()));
+        let both = dir.join("Photos (2)").to_string_lossy().to_string();
+        assert_eq!(organized_path("moved", "Photos", Some(&both)), Some(dir.join("Photos (2)")));
+        assert_eq!(organized_path("moved", "my.project", Some(&folder)), Some(dir.join("my.project")));
+        assert_eq!(organized_path("deleted", "a.zip", None), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pending_hashes_are_taken_once() {
+        remember(Path::new("/downloads/setup.exe"), "abc");
+        remember(Path::new("/downloads/setup.exe"), "def");
+        assert_eq!(take_hash("/downloads/setup.exe").as_deref(), Some("def"));
+        assert_eq!(take_hash("/downloads/setup.exe"), None);
+    }
+}
@@ -0,0 +1,669 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::settings::WatchedFolder;
+use crate::FileEntry;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // Every condition must match for the rule to apply
+    pub conditions: Vec<RuleCondition>,
+    pub action: RuleAction,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    // e.g. ["pdf", "docx"] - case-insensitive, leading dot optional
+    Extension { extensions: Vec<String> },
+    // e.g. "invoice_*.pdf" - matched against the file name only
+    Glob { pattern: String },
+    // e.g. "application/pdf" or "image/*" - sniffed from file contents
+    Mime { mime: String },
+    Size { min: Option<u64>, max: Option<u64> },
+    // Substring of the URL the file was downloaded from
+    SourceUrl { contains: String },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    // Destination folder, may contain template placeholders like {year}
+    Move { destination: String },
+    // New file name template, e.g. "{date} {stem}.{ext}"
+    Rename { template: String },
+    Trash,
+    Keep,
+}
+
+// What a rule would do to a single file
+#[derive(Serialize, Clone)]
+pub struct PlannedAction {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub action: String, // "moved", "renamed", "deleted", "kept"
+    pub source: String,
+    pub destination: Option<String>,
+}
+
+// Sniffed lazily so MIME / source URL lookups only touch the file when a rule needs them
+struct MatchContext<'a> {
+    path: &'a Path,
+    name: &'a str,
+    size: u64,
+    mime: Option<Option<String>>,
+    source_url: Option<Option<String>>,
+}
+
+impl<'a> MatchContext<'a> {
+    fn mime(&mut self) -> Option<&str> {
+        if self.mime.is_none() {
+            let sniffed = infer::get_from_path(self.path)
+                .ok()
+                .flatten()
+                .map(|t| t.mime_type().to_string());
+            self.mime = Some(sniffed);
+        }
+        self.mime.as_ref().and_then(|m| m.as_deref())
+    }
+
+    fn source_url(&mut self) -> Option<&str> {
+        if self.source_url.is_none() {
+            self.source_url = Some(read_source_url(self.path));
+        }
+        self.source_url.as_ref().and_then(|u| u.as_deref())
+    }
+}
+
+fn extension_of(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn condition_matches(condition: &RuleCondition, ctx: &mut MatchContext) -> bool {
+    match condition {
+        RuleCondition::Extension { extensions } => {
+            let ext = extension_of(ctx.name);
+            !ext.is_empty()
+                && extensions
+                    .iter()
+                    .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&ext))
+        }
+        RuleCondition::Glob { pattern } => {
+            let options = glob::MatchOptions {
+                case_sensitive: false,
+                require_literal_separator: false,
+                require_literal_leading_dot: false,
+            };
+            glob::Pattern::new(pattern)
+                .map(|p| p.matches_with(ctx.name, options))
+                .unwrap_or(false)
+        }
+        RuleCondition::Mime { mime } => {
+            let wanted = mime.to_lowercase();
+            match ctx.mime() {
+                Some(actual) => match wanted.strip_suffix("/*") {
+                    Some(prefix) => actual.split('/').next() == Some(prefix),
+                    None => actual == wanted,
+                },
+                None => false,
+            }
+        }
+        RuleCondition::Size { min, max } => {
+            min.is_none_or(|m| ctx.size >= m) && max.is_none_or(|m| ctx.size <= m)
+        }
+        RuleCondition::SourceUrl { contains } => {
+            let needle = contains.to_lowercase();
+            ctx.source_url()
+                .map(|url| url.to_lowercase().contains(&needle))
+                .unwrap_or(false)
+        }
+    }
+}
+
+// The rules a watched folder lets at its files: every rule unless it lists their ids
+pub fn for_folder(rules: &[Rule], rule_ids: Option<&[String]>) -> Vec<Rule> {
+    rules
+        .iter()
+        .filter(|rule| rule_ids.is_none_or(|ids| ids.contains(&rule.id)))
+        .cloned()
+        .collect()
+}
+
+// First enabled rule whose conditions all match wins
+pub fn find_matching_rule<'a>(rules: &'a [Rule], path: &Path, name: &str, size: u64) -> Option<&'a Rule> {
+    let mut ctx = MatchContext {
+        path,
+        name,
+        size,
+        mime: None,
+        source_url: None,
+    };
+
+    rules.iter().find(|rule| {
+        rule.enabled
+            && !rule.conditions.is_empty()
+            && rule.conditions.iter().all(|c| condition_matches(c, &mut ctx))
+    })
+}
+
+// Where the browser recorded the download origin, if anywhere we can read it
+#[cfg(target_os = "windows")]
+fn read_source_url(path: &Path) -> Option<String> {
+    // Browsers write a Zone.Identifier alternate data stream with HostUrl / ReferrerUrl
+    let stream = format!("{}:Zone.Identifier", path.to_string_lossy());
+    let contents = fs::read_to_string(stream).ok()?;
+    let mut referrer = None;
+    for line in contents.lines() {
+        if let Some(url) = line.strip_prefix("HostUrl=") {
+            return Some(url.trim().to_string());
+        }
+        if let Some(url) = line.strip_prefix("ReferrerUrl=") {
+            referrer = Some(url.trim().to_string());
+        }
+    }
+    referrer
+}
+
+// Browsers that follow the freedesktop convention (and `wget --xattr`) store the URL in a user xattr
+#[cfg(target_os = "linux")]
+fn read_source_url(path: &Path) -> Option<String> {
+    let value = read_xattr(path, "user.xdg.origin.url")?;
+    let url = String::from_utf8(value).ok()?;
+    let url = url.trim_end_matches('\0').trim();
+    (!url.is_empty()).then(|| url.to_string())
+}
+
+// Safari, Chrome and Firefox record a binary plist of URLs, the download itself first
+#[cfg(target_os = "macos")]
+fn read_source_url(path: &Path) -> Option<String> {
+    let value = read_xattr(path, "com.apple.metadata:kMDItemWhereFroms")?;
+    let urls: Vec<String> = plist::from_bytes(&value).ok()?;
+    urls.into_iter().find(|url| !url.trim().is_empty())
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+fn read_source_url(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn getxattr(path: &CStr, name: &CStr, buffer: &mut [u8]) -> isize {
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len()) }
+}
+
+#[cfg(target_os = "macos")]
+fn getxattr(path: &CStr, name: &CStr, buffer: &mut [u8]) -> isize {
+    unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.as_mut_ptr().cast(), buffer.len(), 0, 0) }
+}
+
+// The raw value of an extended attribute, None if it isn't set or can't be read
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn read_xattr(path: &Path, name: &str) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let name = CString::new(name).ok()?;
+    // An empty buffer asks for the size
+    let size = getxattr(&path, &name, &mut []);
+    if size <= 0 {
+        return None;
+    }
+    let mut value = vec![0u8; size as usize];
+    let read = getxattr(&path, &name, &mut value);
+    if read < 0 {
+        return None;
+    }
+    value.truncate(read as usize);
+    Some(value)
+}
+
+// Supported placeholders: {name} {stem} {ext} {date} {time} {year} {month} {day}
+pub fn render_template(template: &str, name: &str) -> String {
+    let now = chrono::Local::now();
+    let stem = Path::new(name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string());
+    let ext = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    template
+        .replace("{name}", name)
+        .replace("{stem}", &stem)
+        .replace("{ext}", &ext)
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string())
+        .replace("{year}", &now.format("%Y").to_string())
+        .replace("{month}", &now.format("%m").to_string())
+        .replace("{day}", &now.format("%d").to_string())
+}
+
+pub fn plan(rule: &Rule, path: &Path, name: &str) -> PlannedAction {
+    let (action, destination) = match &rule.action {
+        RuleAction::Move { destination } => {
+            let folder = PathBuf::from(render_template(destination, name));
+            ("moved", Some(folder.join(name)))
+        }
+        RuleAction::Rename { template } => {
+            let new_name = render_template(template, name);
+            let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            ("renamed", Some(parent.join(new_name)))
+        }
+        RuleAction::Trash => ("deleted", None),
+        RuleAction::Keep => ("kept", None),
+    };
+
+    PlannedAction {
+        rule_id: rule.id.clone(),
+        rule_name: rule.name.clone(),
+        action: action.to_string(),
+        source: path.to_string_lossy().to_string(),
+        destination: destination.map(|d| d.to_string_lossy().to_string()),
+    }
+}
+
+impl PlannedAction {
+    // Where the file is once the action went through; None if it's gone
+    pub fn final_path(&self) -> Option<&str> {
+        match self.action.as_str() {
+            "kept" => Some(&self.source),
+            "deleted" => None,
+            _ => self.destination.as_deref(),
+        }
+    }
+}
+
+// Returns the trash id when the file was sent to the trash, for its history entry
+pub fn apply(planned: &PlannedAction) -> Result<Option<String>, String> {
+    match (planned.action.as_str(), &planned.destination) {
+        ("moved", Some(destination)) => {
+            crate::move_file(planned.source.clone(), destination.clone(), None, None).map(|_| None)
+        }
+        ("renamed", Some(destination)) => {
+            // A template containing separators would escape the source folder
+            if Path::new(destination).parent() != Path::new(&planned.source).parent() {
+                return Err("Rename template cannot contain path separators".to_string());
+            }
+            if Path::new(destination).exists() {
+                return Err(format!("Rename target already exists: {}", destination));
+            }
+            fs::rename(&planned.source, destination).map_err(|e| e.to_string())?;
+            crate::journal::record(
+                crate::journal::Operation::Move {
+                    from: planned.source.clone(),
+                    to: destination.clone(),
+                    created_dirs: Vec::new(),
+                },
+                None,
+            );
+            Ok(None)
+        }
+        ("deleted", _) => crate::delete_file(planned.source.clone(), None),
+        ("kept", _) => Ok(None),
+        _ => Err(format!("Cannot apply action: {}", planned.action)),
+    }
+}
+
+#[derive(Serialize)]
+pub struct PreviewItem {
+    #[serde(flatten)]
+    pub planned: PlannedAction,
+    // Why the action would not go through cleanly, if anything
+    pub conflict: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SkippedFile {
+    pub name: String,
+    pub path: String,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct RulePreview {
+    pub planned: Vec<PreviewItem>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+// Windows and macOS treat names that differ only in case as the same file, Linux doesn't
+pub fn destination_key(destination: &str) -> String {
+    if cfg!(any(windows, target_os = "macos")) {
+        destination.to_lowercase()
+    } else {
+        destination.to_string()
+    }
+}
+
+// Dry run over the watched folders' contents - same folder, rule selection, matching and
+// planning as the watcher, no disk writes
+pub fn preview(rules: &[Rule], folders: &[WatchedFolder], entries: &[FileEntry]) -> RulePreview {
+    let mut planned = Vec::new();
+    let mut skipped = Vec::new();
+    let mut claimed: HashSet<String> = HashSet::new();
+
+    for entry in entries {
+        let folder = crate::watcher::folder_for(folders, Path::new(&entry.path));
+        let skip_reason = if folder.is_none() {
+            Some("Not in a watched folder")
+        } else if entry.is_dir {
+            Some("Directory")
+        } else if entry.name.starts_with('.') || crate::completion::temp_target(Path::new(&entry.path)).is_some() {
+            Some("Temporary or incomplete download")
+        } else {
+            None
+        };
+        if let Some(reason) = skip_reason {
+            skipped.push(SkippedFile {
+                name: entry.name.clone(),
+                path: entry.path.clone(),
+                reason: reason.to_string(),
+            });
+            continue;
+        }
+
+        let path = Path::new(&entry.path);
+        let allowed = for_folder(rules, folder.and_then(|f| f.rules.as_deref()));
+        let rule = match find_matching_rule(&allowed, path, &entry.name, entry.size) {
+            Some(rule) => rule,
+            None => {
+                skipped.push(SkippedFile {
+                    name: entry.name.clone(),
+                    path: entry.path.clone(),
+                    reason: "No matching rule (would prompt)".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let action = plan(rule, path, &entry.name);
+        let conflict = action.destination.as_ref().and_then(|destination| {
+            if !claimed.insert(destination_key(destination)) {
+                Some("Another file is planned for the same destination".to_string())
+            } else if Path::new(destination).exists() {
+                Some("Destination already exists".to_string())
+            } else if action.action == "renamed" && Path::new(destination).parent() != path.parent() {
+                Some("Rename template cannot contain path separators".to_string())
+            } else {
+                None
+            }
+        });
+
+        planned.push(PreviewItem {
+            planned: action,
+            conflict,
+        });
+    }
+
+    RulePreview { planned, skipped }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, conditions: Vec<RuleCondition>, action: RuleAction) -> Rule {
+        Rule {
+            id: id.to_string(),
+            name: format!("Rule {}", id),
+            enabled: true,
+            conditions,
+            action,
+        }
+    }
+
+    fn watched(dir: &Path, rules: Option<&[&str]>) -> WatchedFolder {
+        WatchedFolder {
+            path: dir.to_string_lossy().to_string(),
+            recursive: false,
+            rules: rules.map(|ids| ids.iter().map(|id| id.to_string()).collect()),
+        }
+    }
+
+    fn extensions(list: &[&str]) -> RuleCondition {
+        RuleCondition::Extension {
+            extensions: list.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn matching<'a>(rules: &'a [Rule], name: &str, size: u64) -> Option<&'a str> {
+        let path = Path::new("downloads").join(name);
+        find_matching_rule(rules, &path, name, size).map(|r| r.id.as_str())
+    }
+
+    #[test]
+    fn matches_extension_glob_and_size() {
+        let by_extension = [rule("docs", vec![extensions(&[".PDF", "docx"])], RuleAction::Keep)];
+        assert_eq!(matching(&by_extension, "report.pdf", 1), Some("docs"));
+        assert_eq!(matching(&by_extension, "Letter.DOCX", 1), Some("docs"));
+        assert_eq!(matching(&by_extension, "pdf", 1), None);
+        assert_eq!(matching(&by_extension, "report.pdf.zip", 1), None);
+
+        let by_glob = [rule("invoices", vec![RuleCondition::Glob { pattern: "invoice_*.pdf".to_string() }], RuleAction::Keep)];
+        assert_eq!(matching(&by_glob, "Invoice_2026-03.PDF", 1), Some("invoices"));
+        assert_eq!(matching(&by_glob, "my_invoice_1.pdf", 1), None);
+
+        let by_size = [rule("big", vec![RuleCondition::Size { min: Some(100), max: Some(200) }], RuleAction::Keep)];
+        assert_eq!(matching(&by_size, "a.iso", 100), Some("big"));
+        assert_eq!(matching(&by_size, "a.iso", 200), Some("big"));
+        assert_eq!(matching(&by_size, "a.iso", 99), None);
+        assert_eq!(matching(&by_size, "a.iso", 201), None);
+
+        // Every condition has to hold
+        let both = [rule("big-pdf", vec![extensions(&["pdf"]), RuleCondition::Size { min: Some(100), max: None }], RuleAction::Keep)];
+        assert_eq!(matching(&both, "a.pdf", 500), Some("big-pdf"));
+        assert_eq!(matching(&both, "a.pdf", 50), None);
+        assert_eq!(matching(&both, "a.zip", 500), None);
+    }
+
+    #[test]
+    fn matches_sniffed_mime_type() {
+        let dir = std::env::temp_dir().join(format!("fileforge-rules-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Named like a text file, but it's a PDF
+        let path = dir.join("notes.txt");
+        fs::write(&path, b"%PDF-1.7\n%fake").unwrap();
+
+        let exact = [rule("pdf", vec![RuleCondition::Mime { mime: "application/PDF".to_string() }], RuleAction::Keep)];
+        let family = [rule("app", vec![RuleCondition::Mime { mime: "application/*".to_string() }], RuleAction::Keep)];
+        let images = [rule("img", vec![RuleCondition::Mime { mime: "image/*".to_string() }], RuleAction::Keep)];
+        assert!(find_matching_rule(&exact, &path, "notes.txt", 14).is_some());
+        assert!(find_matching_rule(&family, &path, "notes.txt", 14).is_some());
+        assert!(find_matching_rule(&images, &path, "notes.txt", 14).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn matches_source_url_from_xattr() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join(format!("fileforge-rules-xattr-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("invoice.pdf");
+        fs::write(&path, b"%PDF-1.7").unwrap();
+
+        let rules = [rule("bank", vec![RuleCondition::SourceUrl { contains: "MyBank.example".to_string() }], RuleAction::Keep)];
+        assert!(find_matching_rule(&rules, &path, "invoice.pdf", 8).is_none());
+
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let url = b"https://mybank.example/statements/invoice.pdf";
+        let set = unsafe {
+            libc::setxattr(c_path.as_ptr(), c"user.xdg.origin.url".as_ptr(), url.as_ptr().cast(), url.len(), 0)
+        };
+        assert_eq!(set, 0, "{}", std::io::Error::last_os_error());
+
+        assert_eq!(read_source_url(&path).as_deref(), Some("https://mybank.example/statements/invoice.pdf"));
+        assert!(find_matching_rule(&rules, &path, "invoice.pdf", 8).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn first_enabled_rule_wins() {
+        let mut rules = vec![
+            rule("off", vec![extensions(&["pdf"])], RuleAction::Trash),
+            rule("empty", Vec::new(), RuleAction::Trash),
+            rule("pdf", vec![extensions(&["pdf"])], RuleAction::Keep),
+            rule("any-pdf", vec![RuleCondition::Glob { pattern: "*.pdf".to_string() }], RuleAction::Keep),
+        ];
+        rules[0].enabled = false;
+
+        // A rule without conditions would match everything, so it never does
+        assert_eq!(matching(&rules, "a.pdf", 1), Some("pdf"));
+        assert_eq!(matching(&rules, "a.zip", 1), None);
+
+        rules[0].enabled = true;
+        assert_eq!(matching(&rules, "a.pdf", 1), Some("off"));
+    }
+
+    #[test]
+    fn plans_each_action() {
+        let path = Path::new("downloads").join("scan.pdf");
+        let plan_for = |action: RuleAction| plan(&rule("r", vec![extensions(&["pdf"])], action), &path, "scan.pdf");
+        let year = chrono::Local::now().format("%Y").to_string();
+
+        let moved = plan_for(RuleAction::Move { destination: "archive/{year}".to_string() });
+        assert_eq!(moved.action, "moved");
+        assert_eq!(moved.rule_name, "Rule r");
+        assert_eq!(moved.source, path.to_string_lossy());
+        let expected = Path::new("archive").join(&year).join("scan.pdf");
+        assert_eq!(moved.destination.as_deref(), Some(expected.to_string_lossy().as_ref()));
+
+        let renamed = plan_for(RuleAction::Rename { template: "{year} {stem}-copy.{ext}".to_string() });
+        assert_eq!(renamed.action, "renamed");
+        let expected = Path::new("downloads").join(format!("{} scan-copy.pdf", year));
+        assert_eq!(renamed.destination.as_deref(), Some(expected.to_string_lossy().as_ref()));
+
+        let trashed = plan_for(RuleAction::Trash);
+        assert_eq!((trashed.action.as_str(), trashed.destination), ("deleted", None));
+        let kept = plan_for(RuleAction::Keep);
+        assert_eq!((kept.action.as_str(), kept.destination), ("kept", None));
+    }
+
+    fn entry(dir: &Path, name: &str, is_dir: bool) -> FileEntry {
+        FileEntry {
+            name: name.to_string(),
+            path: dir.join(name).to_string_lossy().to_string(),
+            is_dir,
+            size: 10,
+            details: None,
+        }
+    }
+
+    #[test]
+    fn preview_plans_without_touching_disk() {
+        let dir = std::env::temp_dir().join(format!("fileforge-preview-{}", std::process::id()));
+        let archive = dir.join("archive");
+        fs::create_dir_all(&archive).unwrap();
+        fs::write(archive.join("taken.pdf"), "").unwrap();
+        fs::write(dir.join("taken.pdf"), "").unwrap();
+
+        let rules = [
+            rule("rename", vec![extensions(&["txt"])], RuleAction::Rename { template: "sub/{name}".to_string() }),
+            rule("pdf", vec![extensions(&["pdf"])], RuleAction::Move { destination: archive.to_string_lossy().to_string() }),
+        ];
+        let entries = [
+            entry(&dir, "archive", true),
+            entry(&dir, ".hidden.pdf", false),
+            entry(&dir, "movie.mkv.part", false),
+            entry(&dir, "song.mp3", false),
+            entry(&dir, "a.pdf", false),
+            entry(&dir, "taken.pdf", false),
+            entry(&dir, "notes.txt", false),
+        ];
+        let preview = preview(&rules, &[watched(&dir, None)], &entries);
+
+        let skipped: Vec<(&str, &str)> = preview.skipped.iter().map(|s| (s.name.as_str(), s.reason.as_str())).collect();
+        assert_eq!(
+            skipped,
+            [
+                ("archive", "Directory"),
+                (".hidden.pdf", "Temporary or incomplete download"),
+                ("movie.mkv.part", "Temporary or incomplete download"),
+                ("song.mp3", "No matching rule (would prompt)"),
+            ]
+        );
+
+        let planned: Vec<(&str, Option<&str>)> = preview
+            .planned
+            .iter()
+            .map(|p| (p.planned.rule_id.as_str(), p.conflict.as_deref()))
+            .collect();
+        assert_eq!(
+            planned,
+            [
+                ("pdf", None),
+                ("pdf", Some("Destination already exists")),
+                ("rename", Some("Rename template cannot contain path separators")),
+            ]
+        );
+        // Nothing moved
+        assert!(!archive.join("a.pdf").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn preview_flags_files_planned_for_one_destination() {
+        let dir = Path::new("downloads");
+        let rules = [rule("all", vec![extensions(&["pdf"])], RuleAction::Rename { template: "same.pdf".to_string() })];
+        let entries = [entry(dir, "a.pdf", false), entry(dir, "b.pdf", false)];
+        let same = preview(&rules, &[watched(dir, None)], &entries);
+        let conflicts: Vec<Option<&str>> = same.planned.iter().map(|p| p.conflict.as_deref()).collect();
+        assert_eq!(conflicts, [None, Some("Another file is planned for the same destination")]);
+
+        // Names differing only in case are separate files on Linux
+        let rules = [rule("all", vec![extensions(&["pdf"])], RuleAction::Move { destination: "out".to_string() })];
+        let entries = [entry(dir, "a.PDF", false), entry(dir, "a.pdf", false)];
+        let clashes = preview(&rules, &[watched(dir, None)], &entries).planned.iter().filter(|p| p.conflict.is_some()).count();
+        assert_eq!(clashes, if cfg!(any(windows, target_os = "macos")) { 1 } else { 0 });
+    }
+
+    #[test]
+    fn preview_uses_each_folders_rules() {
+        let (downloads, desktop) = (Path::new("downloads"), Path::new("desktop"));
+        let rules = [
+            rule("pdf", vec![extensions(&["pdf"])], RuleAction::Keep),
+            rule("any", vec![], RuleAction::Keep),
+        ];
+        let folders = [watched(downloads, Some(&["any"])), watched(desktop, Some(&[]))];
+        let entries = [
+            entry(downloads, "a.pdf", false),
+            entry(desktop, "b.pdf", false),
+            entry(Path::new("elsewhere"), "c.pdf", false),
+        ];
+
+        let preview = preview(&rules, &folders, &entries);
+        let planned: Vec<(&str, &str)> = preview.planned.iter().map(|p| (p.planned.rule_id.as_str(), p.planned.source.as_str())).collect();
+        assert_eq!(planned, [("any", entries[0].path.as_str())]);
+        let skipped: Vec<&str> = preview.skipped.iter().map(|s| s.reason.as_str()).collect();
+        assert_eq!(skipped, ["No matching rule (would prompt)", "Not in a watched folder"]);
+    }
+
+    #[test]
+    fn renders_templates() {
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(render_template("{date} {name}", "a.tar.gz"), format!("{} a.tar.gz", today));
+        assert_eq!(render_template("{stem}|{ext}", "a.tar.gz"), "a.tar|gz");
+        assert_eq!(render_template("{stem}|{ext}", "Makefile"), "Makefile|");
+    }
+}
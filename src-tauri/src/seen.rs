@@ -0,0 +1,218 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+use crate::completion;
+use crate::history_db::{sql_err, with_db};
+use crate::settings::WatchedFolder;
+
+// A file in a watched folder as FileForge last saw it. Anything present on startup
+// that doesn't match is new, even if it kept its name (a re-download overwrote it).
+#[derive(Clone, PartialEq, Debug)]
+pub struct SeenFile {
+    pub path: String,
+    pub size: u64,
+    // Seconds since the Unix epoch
+    pub modified: Option<i64>,
+}
+
+pub fn stat(path: &Path) -> Option<SeenFile> {
+    let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some(SeenFile {
+        path: path.to_string_lossy().to_string(),
+        size: metadata.len(),
+        modified: metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64),
+    })
+}
+
+fn mark_with(conn: &Connection, file: &SeenFile) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO seen (path, size, modified) VALUES (?1, ?2, ?3)",
+        params![file.path, file.size as i64, file.modified],
+    )?;
+    Ok(())
+}
+
+pub fn mark(file: &SeenFile) -> Result<(), String> {
+    with_db(|conn| mark_with(conn, file).map_err(sql_err))
+}
+
+// Same filtering as the watcher: hidden files and downloads in progress don't count
+fn list_folder(folder: &WatchedFolder) -> Vec<SeenFile> {
+    let mut walker = WalkDir::new(&folder.path).min_depth(1);
+    if !folder.recursive {
+        walker = walker.max_depth(1);
+    }
+    walker
+        .into_iter()
+        .filter_entry(|e| !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && completion::temp_target(e.path()).is_none())
+        .filter_map(|e| stat(e.path()))
+        .collect()
+}
+
+fn in_folder(path: &str, folder: &WatchedFolder) -> bool {
+    let (path, root) = (Path::new(path), Path::new(&folder.path));
+    if folder.recursive {
+        path.starts_with(root)
+    } else {
+        path.parent() == Some(root)
+    }
+}
+
+fn catch_up_with(conn: &mut Connection, folder: &WatchedFolder, present: Vec<SeenFile>) -> rusqlite::Result<Vec<SeenFile>> {
+    let tx = conn.transaction()?;
+    let known_folder = tx
+        .query_row("SELECT 1 FROM seen_folders WHERE path = ?1", params![folder.path], |_| Ok(()))
+        .optional()?
+        .is_some();
+
+    let seen: HashMap<String, SeenFile> = {
+        let mut stmt = tx.prepare("SELECT path, size, modified FROM seen WHERE substr(path, 1, length(?1)) = ?1")?;
+        let rows = stmt.query_map(params![folder.path], |row| {
+            Ok(SeenFile {
+                path: row.get(0)?,
+                size: row.get::<_, i64>(1)?.max(0) as u64,
+                modified: row.get(2)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|f| in_folder(&f.path, folder))
+            .map(|f| (f.path.clone(), f))
+            .collect()
+    };
+
+    let new_files: Vec<SeenFile> = if known_folder {
+        present.iter().filter(|f| seen.get(&f.path) != Some(*f)).cloned().collect()
+    } else {
+        Vec::new()
+    };
+
+    // Forget what's gone, so the set only ever holds what's in the folder
+    let present_paths: HashSet<&String> = present.iter().map(|f| &f.path).collect();
+    for path in seen.keys().filter(|path| !present_paths.contains(path)) {
+        tx.execute("DELETE FROM seen WHERE path = ?1", params![path])?;
+    }
+    for file in &present {
+        mark_with(&tx, file)?;
+    }
+    if !known_folder {
+        tx.execute(
+            "INSERT INTO seen_folders (path, since) VALUES (?1, ?2)",
+            params![folder.path, chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()],
+        )?;
+    }
+    tx.commit()?;
+    Ok(new_files)
+}
+
+// Files that arrived in `folder` while FileForge wasn't watching it, oldest first. The first
+// scan of a folder only takes stock, so adding a full folder doesn't flood the inbox.
+pub fn catch_up(folder: &WatchedFolder) -> Result<Vec<SeenFile>, String> {
+    let present = list_folder(folder);
+    let mut new_files = with_db(|conn| catch_up_with(conn, folder, present).map_err(sql_err))?;
+    new_files.sort_by_key(|f| f.modified);
+    Ok(new_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64, modified: i64) -> SeenFile {
+        SeenFile {
+            path: path.to_string(),
+            size,
+            modified: Some(modified),
+        }
+    }
+
+    #[test]
+    fn finds_files_that_arrived_while_closed() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::history_db::init_schema(&mut conn).unwrap();
+        let folder = WatchedFolder {
+            path: "d".to_string(),
+            recursive: false,
+            rules: None,
+        };
+        let path = |name: &str| Path::new("d").join(name).to_string_lossy().to_string();
+
+        // First run only takes stock
+        let first = vec![file(&path("old.zip"), 10, 100), file(&path("kept.pdf"), 20, 100)];
+        assert!(catch_up_with(&mut conn, &folder, first).unwrap().is_empty());
+
+        // old.zip was dealt with, report.pdf is new and kept.pdf was overwritten
+        let second = vec![
+            file(&path("kept.pdf"), 25, 300),
+            file(&path("report.pdf"), 5, 200),
+        ];
+        let found = catch_up_with(&mut conn, &folder, second.clone()).unwrap();
+        let names: Vec<&str> = found.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&path("kept.pdf").as_str()) && names.contains(&path("report.pdf").as_str()));
+
+        // Nothing changed since, so nothing to catch up on
+        assert!(catch_up_with(&mut conn, &folder, second).unwrap().is_empty());
+        let rows: i64 = conn.query_row("SELECT COUNT(*) FROM seen", [], |row| row.get(0)).unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn renamed_files_are_not_caught_up_again() {
+        let root = std::env::temp_dir().join(format!("fileforge-seen-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::history_db::init_schema(&mut conn).unwrap();
+        let folder = WatchedFolder {
+            path: root.to_string_lossy().to_string(),
+            recursive: false,
+            rules: None,
+        };
+        let rule = crate::rules::Rule {
+            id: "dated".to_string(),
+            name: "Date prefix".to_string(),
+            enabled: true,
+            conditions: Vec::new(),
+            action: crate::rules::RuleAction::Rename {
+                template: "{date}_{name}".to_string(),
+            },
+        };
+        // What the watcher does with each file catch-up turns up
+        let run_rule = |conn: &Connection, found: Vec<SeenFile>| {
+            for file in found {
+                let path = Path::new(&file.path);
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                let planned = crate::rules::plan(&rule, path, &name);
+                std::fs::rename(&planned.source, planned.destination.as_ref().unwrap()).unwrap();
+                let landed = stat(Path::new(planned.final_path().unwrap())).unwrap();
+                mark_with(conn, &landed).unwrap();
+            }
+        };
+
+        assert!(catch_up_with(&mut conn, &folder, list_folder(&folder)).unwrap().is_empty());
+        std::fs::write(root.join("scan.pdf"), b"x").unwrap();
+        let found = catch_up_with(&mut conn, &folder, list_folder(&folder)).unwrap();
+        assert_eq!(found.len(), 1);
+        run_rule(&conn, found);
+
+        // The renamed file was remembered, so a restart doesn't rename it again
+        assert!(catch_up_with(&mut conn, &folder, list_folder(&folder)).unwrap().is_empty());
+        let names: Vec<String> = std::fs::read_dir(&root)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec![format!("{}_scan.pdf", chrono::Local::now().format("%Y-%m-%d"))]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// None means unlimited
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RetentionLimit {
+    pub max_entries: Option<usize>,
+    pub max_age_days: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryRetention {
+    // Shared by every action that has no entry in per_action
+    pub default: RetentionLimit,
+    // e.g. "moved" -> keep 1000, "kept" -> keep 30 days. Each gets its own count.
+    #[serde(default)]
+    pub per_action: BTreeMap<String, RetentionLimit>,
+    // Rolled-off entries go to monthly archives instead of being dropped
+    #[serde(default)]
+    pub archive: bool,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        HistoryRetention {
+            default: RetentionLimit {
+                max_entries: Some(50),
+                max_age_days: None,
+            },
+            per_action: BTreeMap::new(),
+            archive: true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Settings {
+    pub history: HistoryRetention,
+    pub recent_destinations_limit: usize,
+    pub watched_folders: Vec<WatchedFolder>,
+    // How long a new file's size and modified time must hold still before it counts as downloaded
+    pub stable_window_ms: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            history: HistoryRetention::default(),
+            recent_destinations_limit: 5,
+            watched_folders: default_watched_folders(),
+            stable_window_ms: 2000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct WatchedFolder {
+    pub path: String,
+    // Also pick up files in subfolders
+    #[serde(default)]
+    pub recursive: bool,
+    // Ids of the rules that may handle files from this folder. None means every rule,
+    // an empty list means always ask.
+    #[serde(default)]
+    pub rules: Option<Vec<String>>,
+}
+
+// What gets watched out of the box: just Downloads, as before the list existed
+pub fn default_watched_folders() -> Vec<WatchedFolder> {
+    dirs::download_dir()
+        .map(|path| WatchedFolder {
+            path: path.to_string_lossy().to_string(),
+            recursive: false,
+            rules: None,
+        })
+        .into_iter()
+        .collect()
+}
@@ -0,0 +1,96 @@
+use serde::de::DeserializeOwned;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Unique temp names so two saves racing each other never share a file
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+// Write to a temp file, fsync it, then rename over the target so a crash
+// leaves either the old or the new contents - never a truncated file
+fn replace_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = with_suffix(
+        path,
+        &format!(".tmp-{}-{}", std::process::id(), TEMP_COUNTER.fetch_add(1, Ordering::SeqCst)),
+    );
+
+    let result = (|| {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result?;
+
+    // Persist the rename itself (directories can't be opened for syncing on Windows)
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+// The previous version is kept as `<file>.bak` before it gets replaced
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    if path.exists() {
+        fs::copy(path, backup_path(path))
+            .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+    }
+    replace_file(path, contents).map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+}
+
+// Ok(None) if neither the file nor its backup exist yet.
+// A corrupt file is recovered from `.bak`; if that fails too, the corrupt file
+// is moved aside (so the app can start fresh) and the parse error is returned.
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    let backup = backup_path(path);
+
+    let primary_error = match fs::read_to_string(path) {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => format!("{} is corrupted: {}", path.display(), e),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if !backup.exists() {
+                return Ok(None);
+            }
+            format!("{} is missing", path.display())
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+
+    if let Ok(contents) = fs::read_to_string(&backup) {
+        if let Ok(value) = serde_json::from_str(&contents) {
+            eprintln!("{} - recovered from {}", primary_error, backup.display());
+            // Put the good copy back so the next save doesn't rotate the bad one into .bak
+            replace_file(path, contents.as_bytes())
+                .map_err(|e| format!("Failed to restore {}: {}", path.display(), e))?;
+            return Ok(Some(value));
+        }
+    }
+
+    // Both copies are unusable - keep them for inspection, but out of the way
+    let aside = with_suffix(path, &format!(".corrupt-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+    let moved_primary = path.exists() && fs::rename(path, &aside).is_ok();
+    let _ = fs::rename(&backup, backup_path(&aside));
+    if moved_primary {
+        return Err(format!("{} - the backup could not be read either. The damaged file was kept as {}", primary_error, aside.display()));
+    }
+    Err(primary_error)
+}
@@ -0,0 +1,899 @@
+use filetime::FileTime;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::jobs::{JobControl, JobRegistry};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+// Copies of files up to this size are compared byte for byte before the source goes
+const VERIFY_CONTENT_LIMIT: u64 = 1024 * 1024;
+// FAT and exFAT drives only keep modified times to the nearest 2 seconds
+const MTIME_TOLERANCE_SECS: i64 = 2;
+
+#[derive(Serialize, Clone)]
+struct TransferProgress {
+    job_id: String,
+    source: String,
+    destination: String,
+    bytes_done: u64,
+    total_bytes: u64,
+    bytes_per_sec: f64,
+    eta_secs: Option<f64>,
+    state: String, // "running", "paused", "completed", "cancelled", "failed"
+    error: Option<String>,
+}
+
+struct Progress<'a> {
+    // Sends each update on, as a "transfer-progress" event outside of tests
+    report: &'a dyn Fn(TransferProgress),
+    job_id: &'a str,
+    source: &'a str,
+    destination: &'a str,
+    total_bytes: u64,
+    bytes_done: u64,
+    started: Instant,
+    // Time spent paused doesn't count towards the transfer rate
+    paused_for: Duration,
+    last_emit: Instant,
+}
+
+impl Progress<'_> {
+    fn emit(&mut self, state: &str, error: Option<String>) {
+        let active = self.started.elapsed().saturating_sub(self.paused_for).as_secs_f64();
+        let rate = if active > 0.0 {
+            self.bytes_done as f64 / active
+        } else {
+            0.0
+        };
+        let eta = if rate > 0.0 {
+            Some(self.total_bytes.saturating_sub(self.bytes_done) as f64 / rate)
+        } else {
+            None
+        };
+
+        (self.report)(TransferProgress {
+            job_id: self.job_id.to_string(),
+            source: self.source.to_string(),
+            destination: self.destination.to_string(),
+            bytes_done: self.bytes_done,
+            total_bytes: self.total_bytes,
+            bytes_per_sec: rate,
+            eta_secs: eta,
+            state: state.to_string(),
+            error,
+        });
+        self.last_emit = Instant::now();
+    }
+
+    fn tick(&mut self) {
+        if self.last_emit.elapsed() >= PROGRESS_INTERVAL {
+            self.emit("running", None);
+        }
+    }
+
+    // Waits out a pause, reporting it. Returns false if the job was cancelled.
+    fn checkpoint(&mut self, control: &JobControl) -> bool {
+        if control.is_paused() {
+            self.emit("paused", None);
+            let pause_started = Instant::now();
+            let keep_going = control.checkpoint();
+            self.paused_for += pause_started.elapsed();
+            if keep_going {
+                self.emit("running", None);
+            }
+            return keep_going;
+        }
+        !control.is_cancelled()
+    }
+}
+
+fn copy_times(source: &Path, destination: &Path) {
+    if let Ok(metadata) = fs::metadata(source) {
+        let atime = FileTime::from_last_access_time(&metadata);
+        let mtime = FileTime::from_last_modification_time(&metadata);
+        let _ = filetime::set_file_times(destination, atime, mtime);
+    }
+}
+
+// Blocking copy of a single file that keeps its timestamps
+pub fn copy_file_preserving(source: &Path, destination: &Path) -> Result<(), String> {
+    fs::copy(source, destination).map_err(|e| format!("Copy failed: {}", e))?;
+    copy_times(source, destination);
+    Ok(())
+}
+
+fn copy_symlink(source: &Path, destination: &Path) -> Result<(), String> {
+    let target = fs::read_link(source).map_err(|e| format!("Cannot read link {}: {}", source.display(), e))?;
+
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(&target, destination);
+    #[cfg(windows)]
+    let result = if source.is_dir() {
+        std::os::windows::fs::symlink_dir(&target, destination)
+    } else {
+        std::os::windows::fs::symlink_file(&target, destination)
+    };
+
+    result.map_err(|e| format!("Cannot create link {}: {}", destination.display(), e))
+}
+
+// Recursively copies a folder to a new `destination`, keeping timestamps, permissions
+// and symlinks. Regular files go through `copy_file` so callers can stream them.
+// Ok(false) means `copy_file` reported a cancel.
+pub fn copy_tree(source: &Path, destination: &Path, copy_file: &mut dyn FnMut(&Path, &Path) -> Result<bool, String>) -> Result<bool, String> {
+    fs::create_dir(destination).map_err(|e| format!("Cannot create {}: {}", destination.display(), e))?;
+
+    let entries = fs::read_dir(source).map_err(|e| format!("Cannot read {}: {}", source.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        let from = entry.path();
+        let to = destination.join(entry.file_name());
+
+        if file_type.is_symlink() {
+            copy_symlink(&from, &to)?;
+        } else if file_type.is_dir() {
+            if !copy_tree(&from, &to, copy_file)? {
+                return Ok(false);
+            }
+        } else {
+            if !copy_file(&from, &to)? {
+                return Ok(false);
+            }
+            copy_times(&from, &to);
+        }
+    }
+
+    // Adding children bumps the folder's mtime, so it's restored last
+    if let Ok(metadata) = fs::metadata(source) {
+        let _ = fs::set_permissions(destination, metadata.permissions());
+    }
+    copy_times(source, destination);
+    Ok(true)
+}
+
+fn same_file(from: &Path, to: &Path, from_meta: &fs::Metadata, to_meta: &fs::Metadata) -> bool {
+    if !to_meta.is_file() || to_meta.len() != from_meta.len() {
+        return false;
+    }
+    let mtime = |m: &fs::Metadata| FileTime::from_last_modification_time(m).unix_seconds();
+    if (mtime(from_meta) - mtime(to_meta)).abs() > MTIME_TOLERANCE_SECS {
+        return false;
+    }
+    if from_meta.len() > VERIFY_CONTENT_LIMIT {
+        return true;
+    }
+    match (fs::read(from), fs::read(to)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Confirms the copy holds exactly the same entries, each with the right type, size and
+// modified time. Small files are compared in full.
+pub fn verify_tree(source: &Path, destination: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(source)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Cannot read {}: {}", source.display(), e))?;
+    let copied = fs::read_dir(destination)
+        .map_err(|e| format!("Cannot read {}: {}", destination.display(), e))?
+        .count();
+    if copied != entries.len() {
+        return Err(format!(
+            "Verification failed: {} has {} entries instead of {}",
+            destination.display(),
+            copied,
+            entries.len()
+        ));
+    }
+
+    for entry in entries {
+        let from = entry.path();
+        let to = destination.join(entry.file_name());
+
+        let from_meta = fs::symlink_metadata(&from).map_err(|e| e.to_string())?;
+        let to_meta = fs::symlink_metadata(&to)
+            .map_err(|_| format!("Verification failed: {} is missing", to.display()))?;
+
+        let matches = if from_meta.file_type().is_symlink() {
+            to_meta.file_type().is_symlink() && fs::read_link(&from).ok() == fs::read_link(&to).ok()
+        } else if from_meta.is_dir() {
+            to_meta.is_dir()
+        } else {
+            same_file(&from, &to, &from_meta, &to_meta)
+        };
+        if !matches {
+            return Err(format!("Verification failed: {} differs from the original", to.display()));
+        }
+
+        if from_meta.is_dir() {
+            verify_tree(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+// Moves a folder to another drive: copies the whole tree, verifies it and only then
+// removes the source. If the copy fails it goes and the source stays as it was.
+// Ok(Some(error)) means the move is done, but some of the source couldn't be deleted.
+pub fn move_tree(source: &Path, destination: &Path, copy_file: &mut dyn FnMut(&Path, &Path) -> Result<bool, String>) -> Result<Option<String>, String> {
+    // A partial tree is removed on failure, so never copy over an existing folder
+    if destination.exists() {
+        return Err(format!("{} already exists", destination.display()));
+    }
+
+    let copied = copy_tree(source, destination, copy_file).and_then(|completed| {
+        if !completed {
+            return Err("Copy was interrupted".to_string());
+        }
+        verify_tree(source, destination)
+    });
+    if let Err(e) = copied {
+        let _ = fs::remove_dir_all(destination);
+        return Err(format!("Copy failed: {}", e));
+    }
+
+    // Some of the folder may be gone already, so the verified copy has to stay
+    Ok(fs::remove_dir_all(source).err().map(|e| {
+        format!(
+            "Moved to {}, but the original could not be fully deleted: {}",
+            destination.display(),
+            e
+        )
+    }))
+}
+
+// The single-file counterpart of move_tree. A failed copy leaves nothing behind, and if
+// the original can't be deleted the copy goes again, so the file is only ever in one place.
+pub fn move_file_by_copy(source: &Path, destination: &Path) -> Result<(), String> {
+    if fs::symlink_metadata(destination).is_ok() {
+        return Err(format!("{} already exists", destination.display()));
+    }
+
+    if let Err(e) = copy_file_preserving(source, destination) {
+        let _ = fs::remove_file(destination);
+        return Err(e);
+    }
+    fs::remove_file(source).map_err(|e| {
+        let _ = fs::remove_file(destination);
+        format!("Delete original failed: {}", e)
+    })
+}
+
+// Total bytes of regular files under `path` (symlinks are not followed)
+pub fn tree_size(path: &Path) -> u64 {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return 0,
+    };
+    if metadata.is_file() {
+        return metadata.len();
+    }
+    if !metadata.is_dir() {
+        return 0;
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| tree_size(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+// Like fs::rename, but fails rather than replace whatever turned up at `destination` since
+// it was checked. Where the OS can't do that in one step, it's checked right before.
+pub fn rename_no_replace(source: &Path, destination: &Path) -> io::Result<()> {
+    if let Some(result) = rename_exclusive(source, destination) {
+        return result;
+    }
+    if fs::symlink_metadata(destination).is_ok() {
+        return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    }
+    fs::rename(source, destination)
+}
+
+// None when the platform or filesystem has no rename that refuses to replace
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn rename_exclusive(source: &Path, destination: &Path) -> Option<io::Result<()>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(source.as_os_str().as_bytes()).ok()?;
+    let to = CString::new(destination.as_os_str().as_bytes()).ok()?;
+    let result = unsafe {
+        libc::renameat2(libc::AT_FDCWD, from.as_ptr(), libc::AT_FDCWD, to.as_ptr(), libc::RENAME_NOREPLACE)
+    };
+    if result == 0 {
+        return Some(Ok(()));
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::EINVAL | libc::ENOSYS) => None,
+        _ => Some(Err(error)),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn rename_exclusive(source: &Path, destination: &Path) -> Option<io::Result<()>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(source.as_os_str().as_bytes()).ok()?;
+    let to = CString::new(destination.as_os_str().as_bytes()).ok()?;
+    if unsafe { libc::renamex_np(from.as_ptr(), to.as_ptr(), libc::RENAME_EXCL) } == 0 {
+        return Some(Ok(()));
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(libc::ENOTSUP) => None,
+        _ => Some(Err(error)),
+    }
+}
+
+// Without MOVEFILE_REPLACE_EXISTING, MoveFileExW fails on an existing destination
+#[cfg(windows)]
+fn rename_exclusive(source: &Path, destination: &Path) -> Option<io::Result<()>> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::MoveFileExW;
+
+    let wide = |path: &Path| path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let (from, to) = (wide(source), wide(destination));
+    if unsafe { MoveFileExW(from.as_ptr(), to.as_ptr(), 0) } != 0 {
+        return Some(Ok(()));
+    }
+    Some(Err(io::Error::last_os_error()))
+}
+
+#[cfg(not(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos", windows)))]
+fn rename_exclusive(_source: &Path, _destination: &Path) -> Option<io::Result<()>> {
+    None
+}
+
+// Whether moving `source` to `destination` means copying the data to another drive
+// rather than renaming. The destination's folder doesn't have to exist yet.
+pub fn crosses_drives(source: &Path, destination: &Path) -> bool {
+    let Some(existing) = destination.ancestors().skip(1).find(|p| p.exists()) else {
+        return false;
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::symlink_metadata(source), fs::metadata(existing)) {
+            (Ok(a), Ok(b)) => a.dev() != b.dev(),
+            _ => false,
+        }
+    }
+    #[cfg(windows)]
+    {
+        let volume = |path: &Path| {
+            let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+            path.components().next().map(|c| c.as_os_str().to_string_lossy().to_uppercase())
+        };
+        volume(source) != volume(existing)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (source, existing);
+        false
+    }
+}
+
+fn stream_into(reader: &mut File, writer: &mut File, control: &JobControl, progress: &mut Progress) -> Result<bool, String> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+
+    loop {
+        if !progress.checkpoint(control) {
+            return Ok(false);
+        }
+
+        let read = reader.read(&mut buffer).map_err(|e| format!("Read failed: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).map_err(|e| format!("Write failed: {}", e))?;
+
+        progress.bytes_done += read as u64;
+        progress.tick();
+    }
+
+    writer.sync_all().map_err(|e| format!("Write failed: {}", e))?;
+    Ok(true)
+}
+
+// Ok(false) means the copy was cancelled part-way. Either way a half-written file is
+// removed again, while a file that showed up at `destination` in the meantime is never
+// truncated or deleted.
+fn copy_file_chunked(source: &Path, destination: &Path, control: &JobControl, progress: &mut Progress) -> Result<bool, String> {
+    let mut reader = File::open(source).map_err(|e| format!("Cannot open {}: {}", source.display(), e))?;
+    let mut writer = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)
+        .map_err(|e| format!("Cannot create {}: {}", destination.display(), e))?;
+
+    let copied = stream_into(&mut reader, &mut writer, control, progress);
+    drop(writer);
+    if !matches!(copied, Ok(true)) {
+        let _ = fs::remove_file(destination);
+        return copied;
+    }
+
+    if let Ok(metadata) = fs::metadata(source) {
+        let _ = fs::set_permissions(destination, metadata.permissions());
+    }
+    Ok(true)
+}
+
+enum Outcome {
+    Completed,
+    Cancelled,
+}
+
+// With `replace`, the item already at `destination` is swapped out for the finished copy
+fn run(source: &str, destination: &str, is_move: bool, replace: bool, batch_id: Option<String>, control: &JobControl, progress: &mut Progress) -> Result<Outcome, String> {
+    let source_path = Path::new(source);
+    let dest_path = Path::new(destination);
+    let is_dir = fs::symlink_metadata(source_path).map(|m| m.is_dir()).unwrap_or(false);
+
+    // A replaced destination stays where it is until the new copy is complete beside it
+    let target = if replace {
+        crate::conflicts::staging_path(dest_path)
+    } else {
+        dest_path.to_path_buf()
+    };
+    let target_path = target.as_path();
+
+    // Folders are copied into a fresh destination so a failure can remove it wholesale
+    if is_dir && target_path.exists() {
+        return Err(format!("{} already exists", target_path.display()));
+    }
+
+    let created_dirs = match dest_path.parent() {
+        Some(parent) => crate::create_dirs_tracked(parent)?,
+        None => Vec::new(),
+    };
+
+    // Never leave a half-written tree behind, nor the folders made for it.
+    // copy_file_chunked takes care of a half-written single file.
+    let clean_up = || {
+        if is_dir {
+            let _ = fs::remove_dir_all(target_path);
+        }
+        for dir in created_dirs.iter().rev() {
+            let _ = fs::remove_dir(dir);
+        }
+    };
+
+    // Same-drive moves are a rename, no need to stream anything
+    let renamed = is_move && rename_no_replace(source_path, target_path).is_ok();
+    if renamed {
+        progress.bytes_done = progress.total_bytes;
+    } else {
+        let result = if is_dir {
+            copy_tree(source_path, target_path, &mut |from, to| copy_file_chunked(from, to, control, progress))
+                .and_then(|copied| {
+                    if copied {
+                        verify_tree(source_path, target_path)?;
+                    }
+                    Ok(copied)
+                })
+        } else {
+            copy_file_chunked(source_path, target_path, control, progress).inspect(|&copied| {
+                // Done last, as writing bumps the modified time
+                if copied {
+                    copy_times(source_path, target_path);
+                }
+            })
+        };
+
+        match result {
+            Ok(true) => {}
+            Ok(false) => {
+                clean_up();
+                return Ok(Outcome::Cancelled);
+            }
+            Err(e) => {
+                clean_up();
+                return Err(e);
+            }
+        }
+    }
+
+    let trashed = if replace {
+        match crate::conflicts::swap_into_place(target_path, dest_path) {
+            Ok(trashed) => Some(trashed),
+            Err(e) => {
+                if renamed {
+                    let _ = fs::rename(target_path, source);
+                } else {
+                    if !is_dir {
+                        let _ = fs::remove_file(target_path);
+                    }
+                    clean_up();
+                }
+                return Err(e);
+            }
+        }
+    } else {
+        None
+    };
+
+    // The source only goes once everything has been copied, verified and put in place
+    let mut partly_deleted = None;
+    if is_move && !renamed {
+        if is_dir {
+            // Some of the folder may be gone already, so the verified copy stays and the
+            // move is journaled like any other, with the leftovers reported as an error
+            if let Err(e) = fs::remove_dir_all(source) {
+                partly_deleted = Some(format!(
+                    "Moved to {}, but the original could not be fully deleted: {}",
+                    destination, e
+                ));
+            }
+        } else if let Err(e) = fs::remove_file(source) {
+            // Like move_file_by_copy, the file then stays only where it was
+            let _ = fs::remove_file(dest_path);
+            if let Some(trashed) = &trashed {
+                crate::conflicts::restore_replaced(trashed);
+            }
+            clean_up();
+            return Err(format!("Delete original failed: {}", e));
+        }
+    }
+
+    // A replaced item goes to the trash in the same undo step as the transfer. A copy is
+    // journaled after it too, so undo removes the copy before the old item comes back.
+    let mut batch_id = batch_id;
+    if let Some(trashed) = trashed {
+        let batch = batch_id.get_or_insert_with(crate::journal::new_transaction_id).clone();
+        crate::journal::record(trashed, Some(batch.clone()));
+        if !is_move {
+            crate::journal::record(
+                crate::journal::Operation::Copy {
+                    from: source.to_string(),
+                    to: destination.to_string(),
+                },
+                Some(batch),
+            );
+        }
+    }
+    if is_move {
+        crate::journal::record(
+            crate::journal::Operation::Move {
+                from: source.to_string(),
+                to: destination.to_string(),
+                created_dirs,
+            },
+            batch_id,
+        );
+    }
+    match partly_deleted {
+        Some(error) => Err(error),
+        None => Ok(Outcome::Completed),
+    }
+}
+
+// Starts a background copy or move and returns its job id right away
+pub fn start(app: AppHandle, source: String, destination: String, is_move: bool, replace: bool, batch_id: Option<String>) -> Result<String, String> {
+    fs::symlink_metadata(&source).map_err(|e| format!("Cannot read {}: {}", source, e))?;
+    let total_bytes = tree_size(Path::new(&source));
+
+    let (job_id, control) = app.state::<JobRegistry>().start("transfer");
+    let id = job_id.clone();
+
+    std::thread::spawn(move || {
+        let report = |update: TransferProgress| {
+            let _ = app.emit("transfer-progress", update);
+        };
+        let mut progress = Progress {
+            report: &report,
+            job_id: &job_id,
+            source: &source,
+            destination: &destination,
+            total_bytes,
+            bytes_done: 0,
+            started: Instant::now(),
+            paused_for: Duration::ZERO,
+            last_emit: Instant::now(),
+        };
+        progress.emit("running", None);
+
+        match run(&source, &destination, is_move, replace, batch_id, &control, &mut progress) {
+            Ok(Outcome::Completed) => progress.emit("completed", None),
+            Ok(Outcome::Cancelled) => progress.emit("cancelled", None),
+            Err(e) => {
+                eprintln!("Transfer {} failed: {}", job_id, e);
+                progress.emit("failed", Some(e));
+            }
+        }
+
+        app.state::<JobRegistry>().finish(&job_id);
+    });
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn scratch(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("fileforge-transfer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    // src/a.txt, src/nested/b.bin, src/nested/deeper/c.txt, all dated an hour back
+    fn make_tree(root: &Path) -> PathBuf {
+        let source = root.join("src");
+        fs::create_dir_all(source.join("nested/deeper")).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("nested/b.bin"), vec![7u8; 3000]).unwrap();
+        fs::write(source.join("nested/deeper/c.txt"), "gamma").unwrap();
+        let an_hour_ago = FileTime::from_unix_time(FileTime::now().unix_seconds() - 3600, 0);
+        for file in ["a.txt", "nested/b.bin", "nested/deeper/c.txt"] {
+            filetime::set_file_mtime(source.join(file), an_hour_ago).unwrap();
+        }
+        source
+    }
+
+    fn copy_plain(from: &Path, to: &Path) -> Result<bool, String> {
+        copy_file_preserving(from, to).map(|_| true)
+    }
+
+    #[test]
+    fn rename_never_replaces() {
+        let root = scratch("rename");
+        fs::write(root.join("a.txt"), "new").unwrap();
+        fs::write(root.join("b.txt"), "there first").unwrap();
+
+        let refused = rename_no_replace(&root.join("a.txt"), &root.join("b.txt")).unwrap_err();
+        assert_eq!(refused.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "there first");
+
+        rename_no_replace(&root.join("a.txt"), &root.join("c.txt")).unwrap();
+        assert_eq!(fs::read_to_string(root.join("c.txt")).unwrap(), "new");
+        assert!(!root.join("a.txt").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn single_file_move_by_copy() {
+        let root = scratch("single");
+        let source = make_tree(&root).join("a.txt");
+        let destination = root.join("a.txt");
+        let modified = fs::metadata(&source).unwrap().modified().unwrap();
+
+        move_file_by_copy(&source, &destination).unwrap();
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "alpha");
+        assert_eq!(fs::metadata(&destination).unwrap().modified().unwrap(), modified);
+
+        // Never copies over something that's already there
+        let other = root.join("src/nested/deeper/c.txt");
+        assert!(move_file_by_copy(&other, &destination).is_err());
+        assert!(other.exists());
+        assert_eq!(fs::read_to_string(&destination).unwrap(), "alpha");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    // Reading /proc/self/mem from offset 0 fails once the destination has been created
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn failed_single_file_copy_leaves_nothing_behind() {
+        let root = scratch("single-failed");
+        let destination = root.join("mem.bin");
+
+        assert!(move_file_by_copy(Path::new("/proc/self/mem"), &destination).is_err());
+        assert!(!destination.exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn copies_nested_tree_keeping_times() {
+        let root = scratch("nested");
+        let source = make_tree(&root);
+        let destination = root.join("dest");
+
+        assert!(copy_tree(&source, &destination, &mut copy_plain).unwrap());
+        verify_tree(&source, &destination).unwrap();
+        assert_eq!(fs::read_to_string(destination.join("nested/deeper/c.txt")).unwrap(), "gamma");
+        let mtime = |p: &Path| FileTime::from_last_modification_time(&fs::metadata(p).unwrap());
+        assert_eq!(mtime(&destination.join("nested/b.bin")), mtime(&source.join("nested/b.bin")));
+        assert_eq!(tree_size(&destination), 3010);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_symlinks_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = scratch("unix");
+        let source = make_tree(&root);
+        std::os::unix::fs::symlink("a.txt", source.join("link")).unwrap();
+        fs::set_permissions(source.join("a.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+        fs::set_permissions(source.join("nested"), fs::Permissions::from_mode(0o750)).unwrap();
+        let destination = root.join("dest");
+
+        assert!(copy_tree(&source, &destination, &mut copy_plain).unwrap());
+        verify_tree(&source, &destination).unwrap();
+        assert_eq!(fs::read_link(destination.join("link")).unwrap(), Path::new("a.txt"));
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&destination.join("a.txt")), 0o640);
+        assert_eq!(mode(&destination.join("nested")), 0o750);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn verify_catches_bad_copies() {
+        let root = scratch("verify");
+        let source = make_tree(&root);
+        let destination = root.join("dest");
+        copy_tree(&source, &destination, &mut copy_plain).unwrap();
+
+        // Same size, different content
+        let c = destination.join("nested/deeper/c.txt");
+        let c_time = FileTime::from_last_modification_time(&fs::metadata(&c).unwrap());
+        fs::write(&c, "GAMMA").unwrap();
+        filetime::set_file_mtime(&c, c_time).unwrap();
+        assert!(verify_tree(&source, &destination).is_err());
+        fs::write(&c, "gamma").unwrap();
+        filetime::set_file_mtime(&c, c_time).unwrap();
+        verify_tree(&source, &destination).unwrap();
+
+        // Modified time not carried over
+        let a = destination.join("a.txt");
+        filetime::set_file_mtime(&a, FileTime::now()).unwrap();
+        assert!(verify_tree(&source, &destination).is_err());
+        copy_times(&source.join("a.txt"), &a);
+
+        // A stray extra file, then a missing one
+        fs::write(destination.join("nested/extra.txt"), "").unwrap();
+        assert!(verify_tree(&source, &destination).is_err());
+        fs::remove_file(destination.join("nested/extra.txt")).unwrap();
+        fs::remove_file(destination.join("nested/b.bin")).unwrap();
+        assert!(verify_tree(&source, &destination).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn run_copy(source: &Path, destination: &Path, control: &JobControl) -> Result<Outcome, String> {
+        let report = |_: TransferProgress| {};
+        let mut progress = Progress {
+            report: &report,
+            job_id: "transfer-test",
+            source: "",
+            destination: "",
+            total_bytes: tree_size(source),
+            bytes_done: 0,
+            started: Instant::now(),
+            paused_for: Duration::ZERO,
+            last_emit: Instant::now(),
+        };
+        let outcome = run(
+            &source.to_string_lossy(),
+            &destination.to_string_lossy(),
+            false,
+            false,
+            None,
+            control,
+            &mut progress,
+        )?;
+        assert_eq!(progress.bytes_done, if matches!(outcome, Outcome::Completed) { progress.total_bytes } else { 0 });
+        Ok(outcome)
+    }
+
+    #[test]
+    fn streamed_copy_keeps_times() {
+        let root = scratch("stream");
+        let source = make_tree(&root);
+        let file = source.join("nested/b.bin");
+        let destination = root.join("out/b.bin");
+
+        let outcome = run_copy(&file, &destination, &JobControl::default()).unwrap();
+        assert!(matches!(outcome, Outcome::Completed));
+        assert_eq!(fs::read(&destination).unwrap(), vec![7u8; 3000]);
+        let mtime = |p: &Path| FileTime::from_last_modification_time(&fs::metadata(p).unwrap());
+        assert_eq!(mtime(&destination), mtime(&file));
+
+        let outcome = run_copy(&source, &root.join("out/tree"), &JobControl::default()).unwrap();
+        assert!(matches!(outcome, Outcome::Completed));
+        verify_tree(&source, &root.join("out/tree")).unwrap();
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn cancelled_or_failed_copy_cleans_up() {
+        let root = scratch("cleanup");
+        let source = make_tree(&root);
+
+        let jobs = crate::jobs::JobRegistry::default();
+        let (id, control) = jobs.start("transfer");
+        jobs.cancel(&id).unwrap();
+        let outcome = run_copy(&source, &root.join("new/deeper/tree"), &control).unwrap();
+        assert!(matches!(outcome, Outcome::Cancelled));
+        assert!(!root.join("new").exists());
+
+        // Reading the source fails after the destination folders were made
+        let missing = source.join("gone.bin");
+        assert!(run_copy(&missing, &root.join("other/gone.bin"), &JobControl::default()).is_err());
+        assert!(!root.join("other").exists());
+        assert!(source.join("a.txt").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn streamed_copy_never_touches_a_file_already_there() {
+        let root = scratch("create-new");
+        let source = make_tree(&root);
+        // Appeared after the conflict check
+        fs::write(root.join("a.txt"), "someone else's").unwrap();
+
+        assert!(run_copy(&source.join("a.txt"), &root.join("a.txt"), &JobControl::default()).is_err());
+        assert_eq!(fs::read_to_string(root.join("a.txt")).unwrap(), "someone else's");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn same_folder_is_not_another_drive() {
+        let root = scratch("drives");
+        let source = make_tree(&root);
+        assert!(!crosses_drives(&source.join("a.txt"), &root.join("not/yet/made/a.txt")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn move_tree_removes_source_only_when_done() {
+        let root = scratch("move");
+        let source = make_tree(&root);
+        let destination = root.join("dest");
+
+        assert!(move_tree(&source, &destination, &mut copy_plain).unwrap().is_none());
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(destination.join("a.txt")).unwrap(), "alpha");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn failed_move_leaves_source_intact() {
+        let root = scratch("fail");
+        let source = make_tree(&root);
+        let destination = root.join("dest");
+
+        // The second file fails to copy, e.g. the drive filled up
+        let mut copied = 0;
+        let mut flaky = |from: &Path, to: &Path| {
+            copied += 1;
+            if copied == 2 {
+                return Err("No space left on device".to_string());
+            }
+            copy_plain(from, to)
+        };
+        assert!(move_tree(&source, &destination, &mut flaky).is_err());
+        assert!(!destination.exists());
+        assert_eq!(tree_size(&source), 3010);
+        assert_eq!(fs::read_to_string(source.join("nested/deeper/c.txt")).unwrap(), "gamma");
+
+        // A folder already at the destination is never copied into
+        fs::create_dir(&destination).unwrap();
+        assert!(move_tree(&source, &destination, &mut copy_plain).is_err());
+        assert!(source.join("a.txt").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Serialize, Clone)]
+pub struct TrashEntry {
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    pub time_deleted: i64,
+    // None for folders
+    pub size: Option<u64>,
+    pub is_dir: bool,
+    // Timestamp of the "deleted" history entry that put this item in the trash
+    pub history_timestamp: Option<String>,
+}
+
+// trash::os_limited only exists on Windows and freedesktop (Linux/BSD) platforms
+#[cfg(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+))]
+mod os {
+    use super::TrashEntry;
+    use std::path::Path;
+    use trash::{TrashItem, TrashItemSize};
+
+    fn to_entry(item: &TrashItem) -> TrashEntry {
+        let (size, is_dir) = match trash::os_limited::metadata(item) {
+            Ok(metadata) => match metadata.size {
+                TrashItemSize::Bytes(bytes) => (Some(bytes), false),
+                TrashItemSize::Entries(_) => (None, true),
+            },
+            Err(_) => (None, false),
+        };
+
+        TrashEntry {
+            id: item.id.to_string_lossy().to_string(),
+            name: item.name.to_string_lossy().to_string(),
+            original_path: item.original_path().to_string_lossy().to_string(),
+            time_deleted: item.time_deleted,
+            size,
+            is_dir,
+            history_timestamp: None,
+        }
+    }
+
+    fn items_by_id(ids: &[String]) -> Result<Vec<TrashItem>, String> {
+        let items: Vec<TrashItem> = trash::os_limited::list()
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|item| ids.iter().any(|id| item.id.to_string_lossy() == id.as_str()))
+            .collect();
+
+        if items.len() < ids.len() {
+            return Err("Some items are no longer in the trash".to_string());
+        }
+        Ok(items)
+    }
+
+    pub fn list() -> Result<Vec<TrashEntry>, String> {
+        let mut items = trash::os_limited::list().map_err(|e| e.to_string())?;
+        // Most recently deleted first
+        items.sort_by_key(|item| std::cmp::Reverse(item.time_deleted));
+        Ok(items.iter().map(to_entry).collect())
+    }
+
+    // Id of the most recently trashed item that used to live at `path`
+    pub fn find_trash_id(path: &Path) -> Option<String> {
+        trash::os_limited::list()
+            .ok()?
+            .into_iter()
+            .filter(|item| item.original_path() == path)
+            .max_by_key(|item| item.time_deleted)
+            .map(|item| item.id.to_string_lossy().to_string())
+    }
+
+    pub fn restore(id: &str) -> Result<(), String> {
+        restore_many(&[id.to_string()]).map(|_| ())
+    }
+
+    pub fn restore_many(ids: &[String]) -> Result<Vec<TrashEntry>, String> {
+        let items = items_by_id(ids)?;
+        let entries = items.iter().map(to_entry).collect();
+        trash::os_limited::restore_all(items).map_err(|e| match e {
+            trash::Error::RestoreCollision { path, .. } => {
+                format!("Cannot restore: {} already exists", path.to_string_lossy())
+            }
+            other => other.to_string(),
+        })?;
+        Ok(entries)
+    }
+
+    pub fn purge(ids: &[String]) -> Result<(), String> {
+        let items = items_by_id(ids)?;
+        trash::os_limited::purge_all(items).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android"))
+)))]
+mod os {
+    use super::TrashEntry;
+    use std::path::Path;
+
+    const UNSUPPORTED: &str = "Browsing the trash is not supported on this platform";
+
+    pub fn list() -> Result<Vec<TrashEntry>, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn find_trash_id(_path: &Path) -> Option<String> {
+        None
+    }
+
+    pub fn restore(_id: &str) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn restore_many(_ids: &[String]) -> Result<Vec<TrashEntry>, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub fn purge(_ids: &[String]) -> Result<(), String> {
+        Err(UNSUPPORTED.to_string())
+    }
+}
+
+pub fn list() -> Result<Vec<TrashEntry>, String> {
+    os::list()
+}
+
+pub fn find_trash_id(path: &Path) -> Option<String> {
+    os::find_trash_id(path)
+}
+
+pub fn restore(id: &str) -> Result<(), String> {
+    os::restore(id)
+}
+
+// Returns what was restored so callers can log it
+pub fn restore_many(ids: &[String]) -> Result<Vec<TrashEntry>, String> {
+    os::restore_many(ids)
+}
+
+// Permanently deletes the given items
+pub fn purge(ids: &[String]) -> Result<(), String> {
+    os::purge(ids)
+}
+
+#[cfg(test)]
+#[cfg(all(unix, not(target_os = "macos"), not(target_os = "ios"), not(target_os = "android")))]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Set only in the child process that trash_round_trip_with_temp_data_home starts
+    const ROOT_VAR: &str = "FILEFORGE_TRASH_TEST_ROOT";
+
+    // The freedesktop home trash lives under $XDG_DATA_HOME/Trash, and the trash crate
+    // only reads it from the environment. Changing that here would affect every test
+    // running alongside, so the round trip runs in a child process with its own.
+    #[test]
+    fn trash_round_trip_with_temp_data_home() {
+        let root = std::env::temp_dir().join(format!("fileforge-trash-{}", std::process::id()));
+        fs::create_dir_all(root.join("data")).unwrap();
+
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--ignored", "--exact", "trash_bin::tests::trash_round_trip_in_child", "--nocapture"])
+            .env("XDG_DATA_HOME", root.join("data"))
+            .env(ROOT_VAR, &root)
+            .output()
+            .unwrap();
+        let _ = fs::remove_dir_all(&root);
+
+        let log = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}{}", log, String::from_utf8_lossy(&output.stderr));
+        assert!(log.contains("1 passed"), "the round trip didn't run:\n{}", log);
+    }
+
+    // Only meant to run as the child of the test above, never against the real trash
+    #[test]
+    #[ignore = "run by trash_round_trip_with_temp_data_home"]
+    fn trash_round_trip_in_child() {
+        let root = std::env::var_os(ROOT_VAR).expect("run through trash_round_trip_with_temp_data_home");
+        let docs = Path::new(&root).join("docs");
+        fs::create_dir_all(&docs).unwrap();
+
+        let file = docs.join("report.pdf");
+        fs::write(&file, b"hello").unwrap();
+        trash::delete(&file).unwrap();
+        assert!(!file.exists());
+
+        let id = find_trash_id(&file).expect("deleted file should be in the trash");
+        let entry = list().unwrap().into_iter().find(|e| e.id == id).unwrap();
+        assert_eq!(entry.name, "report.pdf");
+        assert_eq!(entry.size, Some(5));
+        assert!(!entry.is_dir);
+
+        let restored = restore_many(&[id]).unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(fs::read(&file).unwrap(), b"hello");
+
+        trash::delete(&file).unwrap();
+        let id = find_trash_id(&file).unwrap();
+        purge(std::slice::from_ref(&id)).unwrap();
+        assert!(find_trash_id(&file).is_none());
+        assert!(restore(&id).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::completion::{self, CompletionTracker, Finished};
+use crate::debounce::{Change, Debouncer};
+use crate::pending::PendingInbox;
+use crate::settings::{Settings, WatchedFolder};
+use crate::{redownload, seen, NewFileEvent};
+
+// How often files in flight are checked for being done
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+// A browser fires several events per download; they're merged once a path is quiet this long
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(500);
+
+// Shared with the event thread, which needs it for every event
+struct WatchState {
+    // What is actually being watched
+    folders: Vec<WatchedFolder>,
+    stable_window: Duration,
+}
+
+// Owns the notify watcher so folders can be added and removed while the app runs
+pub struct FolderWatcher {
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    state: Arc<Mutex<WatchState>>,
+    // For catching up on folders as they start being watched
+    app: Mutex<Option<AppHandle>>,
+}
+
+impl Default for FolderWatcher {
+    fn default() -> Self {
+        FolderWatcher {
+            watcher: Mutex::new(None),
+            app: Mutex::new(None),
+            state: Arc::new(Mutex::new(WatchState {
+                folders: Vec::new(),
+                stable_window: Duration::from_millis(Settings::default().stable_window_ms),
+            })),
+        }
+    }
+}
+
+impl FolderWatcher {
+    pub fn start(&self, app: AppHandle, settings: &Settings) -> Result<(), String> {
+        let (tx, rx) = channel();
+        let watcher = notify::recommended_watcher(move |res: Result<Event, _>| {
+            if let Ok(event) = res {
+                let _ = tx.send(event);
+            }
+        })
+        .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+        *self.watcher.lock().unwrap_or_else(|e| e.into_inner()) = Some(watcher);
+        *self.app.lock().unwrap_or_else(|e| e.into_inner()) = Some(app.clone());
+
+        let state = self.state.clone();
+        std::thread::spawn(move || {
+            let mut debouncer = Debouncer::new(DEBOUNCE_WINDOW);
+            let mut tracker = CompletionTracker::new(state.lock().unwrap_or_else(|e| e.into_inner()).stable_window);
+            let mut last_poll = Instant::now();
+
+            loop {
+                // Sleep for as long as nothing is in flight
+                let idle = debouncer.is_empty() && tracker.is_empty();
+                let timeout = if idle { Duration::from_secs(3600) } else { POLL_INTERVAL };
+                match rx.recv_timeout(timeout) {
+                    Ok(event) => {
+                        println!("Event detected: {:?}", event.kind);
+                        debouncer.push(event, Instant::now());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    // The watcher (and with it the sender) was dropped
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                if last_poll.elapsed() < POLL_INTERVAL {
+                    continue;
+                }
+                last_poll = Instant::now();
+                {
+                    let state = state.lock().unwrap_or_else(|e| e.into_inner());
+                    tracker.set_window(state.stable_window);
+                    for change in debouncer.drain(last_poll) {
+                        track_change(&mut tracker, change, &state.folders, last_poll);
+                    }
+                }
+                for finished in tracker.poll(last_poll, &completion::Disk) {
+                    let rule_ids = {
+                        let state = state.lock().unwrap_or_else(|e| e.into_inner());
+                        // Folders can be dropped from the list while a file is in flight
+                        match folder_for(&state.folders, &finished.path) {
+                            Some(folder) => folder.rules.clone(),
+                            None => continue,
+                        }
+                    };
+                    // Hashing and rules can take a while, and the next file may already be done
+                    let app = app.clone();
+                    std::thread::spawn(move || handle_finished(&app, finished, rule_ids.as_deref()));
+                }
+            }
+        });
+
+        self.apply(settings)
+    }
+
+    // Brings the watch list in line with the settings. A folder that can't be watched
+    // (missing, no access) is reported but doesn't stop the others. Every folder that
+    // starts being watched (all of them on startup) gets a catch-up scan.
+    pub fn apply(&self, settings: &Settings) -> Result<(), String> {
+        let folders = &settings.watched_folders;
+        let mut guard = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+        let watcher = guard.as_mut().ok_or("The file watcher isn't running")?;
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.stable_window = Duration::from_millis(settings.stable_window_ms);
+        let current = &state.folders;
+
+        let same_watch = |a: &WatchedFolder, b: &WatchedFolder| a.path == b.path && a.recursive == b.recursive;
+
+        for old in current.iter() {
+            if !folders.iter().any(|f| same_watch(f, old)) {
+                let _ = watcher.unwatch(Path::new(&old.path));
+                println!("Stopped watching: {}", old.path);
+            }
+        }
+
+        let mut watching: Vec<WatchedFolder> = Vec::new();
+        let mut added = Vec::new();
+        let mut failed = Vec::new();
+        for folder in folders {
+            // Listed twice: the first entry wins
+            if watching.iter().any(|f| f.path == folder.path) {
+                continue;
+            }
+            if !current.iter().any(|f| same_watch(f, folder)) {
+                let mode = if folder.recursive {
+                    RecursiveMode::Recursive
+                } else {
+                    RecursiveMode::NonRecursive
+                };
+                if let Err(e) = watcher.watch(Path::new(&folder.path), mode) {
+                    eprintln!("Failed to watch {}: {}", folder.path, e);
+                    failed.push(folder.path.clone());
+                    continue;
+                }
+                println!("Watching: {}", folder.path);
+                added.push(folder.clone());
+            }
+            // Rule changes take effect without touching the watch itself
+            watching.push(folder.clone());
+        }
+        state.folders = watching;
+
+        if let Some(app) = self.app.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+            if !added.is_empty() {
+                std::thread::spawn(move || catch_up(&app, &added));
+            }
+        }
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Could not watch {}", failed.join(", ")))
+        }
+    }
+}
+
+// The watched folder a new file belongs to - the innermost one when they nest
+pub fn folder_for<'a>(folders: &'a [WatchedFolder], path: &Path) -> Option<&'a WatchedFolder> {
+    folders
+        .iter()
+        .filter(|f| {
+            let root = Path::new(&f.path);
+            if f.recursive {
+                path.starts_with(root) && path != root
+            } else {
+                path.parent() == Some(root)
+            }
+        })
+        .max_by_key(|f| Path::new(&f.path).components().count())
+}
+
+// Only regular, visible files inside a watched folder are worth tracking
+fn is_candidate(path: &Path, folders: &[WatchedFolder]) -> bool {
+    let hidden = path.file_name().is_none_or(|n| n.to_string_lossy().starts_with('.'));
+    !hidden && path.is_file() && folder_for(folders, path).is_some()
+}
+
+fn track_change(tracker: &mut CompletionTracker, change: Change, folders: &[WatchedFolder], now: Instant) {
+    match change {
+        Change::Arrived(path) => {
+            if is_candidate(&path, folders) {
+                tracker.observe(path, now);
+            }
+        }
+        Change::Renamed { from, to } => {
+            if is_candidate(&to, folders) {
+                tracker.rename(&from, to, now);
+            } else {
+                tracker.forget(&from);
+            }
+        }
+        Change::Removed(path) => tracker.forget(&path),
+    }
+}
+
+// Hashes the file and lets the folder's rules at it. Returns what to show the user
+// if no rule took care of it.
+fn examine(app_handle: &AppHandle, path: &Path, size: u64, detected_at: String, rule_ids: Option<&[String]>) -> Option<NewFileEvent> {
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    println!("New download detected: {} ({} bytes)", name, size);
+
+    // Hashed before any rule moves it, so the hash lands in history either way
+    let duplicate_of = redownload::check(path, size);
+    if let Some(earlier) = &duplicate_of {
+        println!("Already organized to {}", earlier.path);
+    }
+
+    // Let a matching rule handle the file instead of prompting
+    if let Some(planned) = crate::auto_organize(app_handle, path, &name, size, &detected_at, rule_ids) {
+        // Otherwise the next catch-up scan takes a renamed or kept file for a new one
+        // and runs the rule on it again
+        if let Some(final_path) = planned.final_path() {
+            remember(Path::new(final_path));
+        }
+        return None;
+    }
+
+    Some(NewFileEvent {
+        name,
+        path: path.to_string_lossy().to_string(),
+        size,
+        detected_at,
+        duplicate_of,
+    })
+}
+
+// So the catch-up scan on the next start knows this file was already dealt with
+fn remember(path: &Path) {
+    if let Some(file) = seen::stat(path) {
+        if let Err(e) = seen::mark(&file) {
+            eprintln!("Could not remember {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn handle_finished(app_handle: &AppHandle, finished: Finished, rule_ids: Option<&[String]>) {
+    let Finished { path, size, detected_at } = finished;
+    let Some(event) = examine(app_handle, &path, size, detected_at, rule_ids) else {
+        return;
+    };
+
+    remember(&path);
+
+    app_handle.state::<PendingInbox>().add(app_handle, vec![event]);
+}
+
+// Files that arrived while a folder wasn't watched go through the rules like any
+// other download, and whatever is left lands in the inbox as one batch
+fn catch_up(app_handle: &AppHandle, folders: &[WatchedFolder]) {
+    let mut batch = Vec::new();
+    for folder in folders {
+        let found = match seen::catch_up(folder) {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("Catch-up scan of {} failed: {}", folder.path, e);
+                continue;
+            }
+        };
+        if !found.is_empty() {
+            println!("{} new files in {} since it was last watched", found.len(), folder.path);
+        }
+        for file in found {
+            // The best guess at when it arrived
+            let detected_at = file
+                .modified
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                .map(|t| t.with_timezone(&chrono::Local))
+                .unwrap_or_else(chrono::Local::now)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string();
+            batch.extend(examine(app_handle, Path::new(&file.path), file.size, detected_at, folder.rules.as_deref()));
+        }
+    }
+    app_handle.state::<PendingInbox>().add(app_handle, batch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(path: &Path, recursive: bool) -> WatchedFolder {
+        WatchedFolder {
+            path: path.to_string_lossy().to_string(),
+            recursive,
+            rules: None,
+        }
+    }
+
+    #[test]
+    fn picks_innermost_matching_folder() {
+        let home = Path::new("home");
+        let folders = vec![
+            folder(&home.join("Downloads"), false),
+            folder(home, true),
+            folder(&home.join("inbox"), true),
+        ];
+
+        let owner = |path: &Path| folder_for(&folders, path).map(|f| f.path.clone());
+        assert_eq!(owner(&home.join("Downloads/a.pdf")), Some(folders[0].path.clone()));
+        // Downloads itself isn't recursive, so its subfolders fall through to home
+        assert_eq!(owner(&home.join("Downloads/sub/a.pdf")), Some(folders[1].path.clone()));
+        assert_eq!(owner(&home.join("inbox/2026/scan.png")), Some(folders[2].path.clone()));
+        assert_eq!(owner(Path::new("elsewhere/a.pdf")), None);
+    }
+}
@@ -5,7 +5,7 @@ import {
   HardDrive, Database, Usb, Folder, File, ArrowLeft, RefreshCw,
   FileText, FileImage, FileVideo, FileAudio, FileCode, FileArchive,
   FileSpreadsheet, Presentation, FileJson, Download, X, FolderOpen,
-  AlertCircle, Check, Trash2, Settings, History, Info
+  AlertCircle, Check, Trash2, Settings, History, Info, Inbox, Pause, Play
 } from "lucide-react";
 import "./App.css";
 
@@ -31,6 +31,20 @@ interface NewFileEvent {
   name: string;
   path: string;
   size: number;
+  detected_at: string;
+  duplicate_of: DuplicateOf | null;
+}
+
+// A download waiting in the inbox
+interface PendingDownload extends NewFileEvent {
+  id: number;
+  queued_at: string;
+}
+
+interface DuplicateOf {
+  path: string;
+  action: string;
+  timestamp: string;
 }
 
 interface DownloadHistoryEntry {
@@ -40,6 +54,22 @@ interface DownloadHistoryEntry {
   timestamp: string;
   action: string;
   destination: string | null;
+  // Set on "deleted" rows whose item went to the trash
+  trash_id?: string | null;
+  detected_at?: string | null;
+}
+
+// Sent with every "transfer-progress" event of a background copy or move
+interface TransferProgress {
+  job_id: string;
+  source: string;
+  destination: string;
+  bytes_done: number;
+  total_bytes: number;
+  bytes_per_sec: number;
+  eta_secs: number | null;
+  state: "running" | "paused" | "completed" | "cancelled" | "failed";
+  error: string | null;
 }
 
 function formatBytes(bytes: number): string {
@@ -204,16 +234,72 @@ function FileEntryRow({
 }
 
 // Move File Modal Component
+function formatEta(secs: number | null): string {
+  if (secs === null) return "";
+  if (secs < 60) return `${Math.ceil(secs)}s left`;
+  return `${Math.ceil(secs / 60)} min left`;
+}
+
+// Cross-drive moves still running, with pause and cancel
+function TransferPanel({ transfers }: { transfers: TransferProgress[] }) {
+  if (transfers.length === 0) return null;
+
+  return (
+    <div className="fixed bottom-4 right-4 w-[360px] bg-gray-800 rounded-xl border border-gray-700 shadow-2xl z-40">
+      {transfers.map((t) => {
+        const name = t.source.split(/[\\/]/).pop();
+        const percent = t.total_bytes > 0 ? (t.bytes_done / t.total_bytes) * 100 : 0;
+        return (
+          <div key={t.job_id} className="p-3 border-b border-gray-700 last:border-b-0">
+            <div className="flex items-center justify-between gap-2">
+              <span className="text-sm text-white truncate">{name}</span>
+              <div className="flex items-center gap-1">
+                <button
+                  onClick={() => invoke(t.state === "paused" ? "resume_job" : "pause_job", { jobId: t.job_id })}
+                  className="p-1 hover:bg-gray-700 rounded transition-all"
+                  title={t.state === "paused" ? "Resume" : "Pause"}
+                >
+                  {t.state === "paused" ? <Play className="w-4 h-4 text-gray-300" /> : <Pause className="w-4 h-4 text-gray-300" />}
+                </button>
+                <button
+                  onClick={() => invoke("cancel_job", { jobId: t.job_id })}
+                  className="p-1 hover:bg-gray-700 rounded transition-all"
+                  title="Cancel"
+                >
+                  <X className="w-4 h-4 text-gray-300" />
+                </button>
+              </div>
+            </div>
+            <div className="mt-2 h-1.5 bg-gray-700 rounded">
+              <div className="h-1.5 bg-blue-500 rounded" style={{ width: `${percent}%` }} />
+            </div>
+            <div className="mt-1 text-xs text-gray-400">
+              {formatBytes(t.bytes_done)} of {formatBytes(t.total_bytes)}
+              {t.state === "paused" ? " • Paused" : ` • ${formatBytes(t.bytes_per_sec)}/s ${formatEta(t.eta_secs)}`}
+            </div>
+          </div>
+        );
+      })}
+    </div>
+  );
+}
+
 function MoveFileModal({
   file,
   onClose,
   onMove,
   onDelete,
+  onDiscard,
+  onKeep,
+  onSnooze,
 }: {
   file: FileEntry | NewFileEvent;
   onClose: () => void;
   onMove: (destination: string) => void;
   onDelete?: () => void;
+  onDiscard?: () => void;
+  onKeep?: () => void;
+  onSnooze?: () => void;
 }) {
   const [drives, setDrives] = useState<DriveInfo[]>([]);
   const [currentPath, setCurrentPath] = useState<string | null>(null);
@@ -279,6 +365,7 @@ function MoveFileModal({
   }
 
   const destinationPath = currentPath || "";
+  const duplicateOf = "duplicate_of" in file ? file.duplicate_of : null;
 
   return (
     <div className="fixed inset-0 bg-black/70 flex items-center justify-center z-50">
@@ -302,6 +389,24 @@ function MoveFileModal({
           </button>
         </div>
 
+        {/* Re-download of a file that was already organized */}
+        {duplicateOf && (
+          <div className="p-4 border-b border-gray-700 bg-yellow-900/30">
+            <p className="text-sm text-yellow-300">
+              Already {duplicateOf.action} to {getDisplayPath(duplicateOf.path)} on {duplicateOf.timestamp}
+            </p>
+            {onDiscard && (
+              <button
+                onClick={onDiscard}
+                className="mt-2 flex items-center gap-2 px-3 py-1.5 bg-red-600 hover:bg-red-700 rounded-lg text-white text-sm transition-all"
+              >
+                <Trash2 className="w-4 h-4" />
+                Discard new copy
+              </button>
+            )}
+          </div>
+        )}
+
         {/* Recent Destinations */}
         {recentDestinations.length > 0 && currentPath === null && (
           <div className="p-4 border-b border-gray-700">
@@ -436,8 +541,17 @@ function MoveFileModal({
             Delete
           </button>
           <div className="flex gap-3">
+            {onSnooze && (
+              <button
+                onClick={onSnooze}
+                className="px-4 py-2 bg-gray-700 hover:bg-gray-600 rounded-lg text-white transition-all"
+                title="Ask again in an hour"
+              >
+                Later
+              </button>
+            )}
             <button
-              onClick={onClose}
+              onClick={onKeep ?? onClose}
               className="px-4 py-2 bg-gray-700 hover:bg-gray-600 rounded-lg text-white transition-all"
             >
               Keep in Downloads
@@ -727,6 +841,8 @@ function App() {
   const fileListRef = useRef<HTMLDivElement>(null);
   const [showHistory, setShowHistory] = useState(false);
   const [downloadHistory, setDownloadHistory] = useState<DownloadHistoryEntry[]>([]);
+  // Trash ids that can still be restored, so history only offers restores that will work
+  const [trashIds, setTrashIds] = useState<Set<string>>(new Set());
   const [showHelp, setShowHelp] = useState(false);
 
 // Load autostart setting on mount
@@ -734,6 +850,17 @@ useEffect(() => {
   loadAutoStartSetting();
 }, []);
 
+// If the saved data couldn't be read, the app is running on default settings
+useEffect(() => {
+  invoke<string | null>("get_startup_error")
+    .then((message) => {
+      if (message) {
+        alert("Your saved FileForge data could not be loaded, so default settings are in use:\n" + message);
+      }
+    })
+    .catch(console.error);
+}, []);
+
 async function loadAutoStartSetting() {
   try {
     const enabled = await invoke<boolean>("get_autostart_enabled");
@@ -755,11 +882,32 @@ async function toggleAutoStart() {
 }
 async function loadDownloadHistory() {
   try {
-    const history = await invoke<DownloadHistoryEntry[]>("get_download_history");
-    setDownloadHistory(history);
+    const page = await invoke<{ entries: DownloadHistoryEntry[]; total: number }>("query_history", {
+      query: { limit: 200 },
+    });
+    setDownloadHistory(page.entries);
   } catch (err) {
     console.error("Failed to load history:", err);
   }
+  try {
+    const trash = await invoke<{ id: string }[]>("list_trash");
+    setTrashIds(new Set(trash.map((item) => item.id)));
+  } catch (err) {
+    // Not every platform lets us look inside the trash
+    setTrashIds(new Set());
+  }
+}
+
+async function restoreFromHistory(entry: DownloadHistoryEntry) {
+  if (!entry.trash_id) return;
+  try {
+    await invoke("restore_from_trash", { ids: [entry.trash_id] });
+    loadDownloadHistory();
+    loadDownloadFiles();
+  } catch (err) {
+    console.error("Failed to restore:", err);
+    alert("Failed to restore: " + err);
+  }
 }
 
 async function clearDownloadHistory() {
@@ -787,22 +935,57 @@ async function clearDownloadHistory() {
   const [lastSelectedIndex, setLastSelectedIndex] = useState<number | null>(null);
   const [showBulkMoveModal, setShowBulkMoveModal] = useState(false);
   
-  // New download popup
-  const [newDownload, setNewDownload] = useState<NewFileEvent | null>(null);
+  // New download popup, showing the oldest download in the inbox
+  const [newDownload, setNewDownload] = useState<PendingDownload | null>(null);
+  const [pending, setPending] = useState<PendingDownload[]>([]);
+
+  // Background moves to another drive, and whoever is waiting for each to finish
+  const [transfers, setTransfers] = useState<TransferProgress[]>([]);
+  const transferWaiters = useRef(new Map<string, (t: TransferProgress) => void>());
+  const finishedTransfers = useRef(new Map<string, TransferProgress>());
 
   useEffect(() => {
     loadDrives();
     loadDownloadsPath();
     
-    // Listen for new downloads
-    const unlisten = listen<NewFileEvent>("new-download", (event) => {
-      console.log("New download detected:", event.payload);
-      setNewDownload(event.payload);
+    // New downloads arrive through the inbox; the popup works through it one at a time
+    function showPending(queued: PendingDownload[]) {
+      setPending(queued);
+      setNewDownload((current) => current ?? queued[0] ?? null);
+    }
+    invoke<PendingDownload[]>("get_pending").then(showPending).catch(console.error);
+    const unlistenPending = listen<PendingDownload[]>("pending-updated", (event) => {
+      console.log("Pending downloads:", event.payload.length);
+      showPending(event.payload);
       loadDownloadFiles();
     });
-    
+
+    // Files handled by an auto-organize rule never open the popup
+    const unlistenOrganized = listen("auto-organized", () => {
+      loadDownloadFiles();
+    });
+
+    const unlistenTransfer = listen<TransferProgress>("transfer-progress", (event) => {
+      const t = event.payload;
+      if (t.state === "running" || t.state === "paused") {
+        setTransfers((current) => [...current.filter((c) => c.job_id !== t.job_id), t]);
+        return;
+      }
+      setTransfers((current) => current.filter((c) => c.job_id !== t.job_id));
+      const waiter = transferWaiters.current.get(t.job_id);
+      if (waiter) {
+        transferWaiters.current.delete(t.job_id);
+        waiter(t);
+      } else {
+        // Finished before moveFile started waiting for it
+        finishedTransfers.current.set(t.job_id, t);
+      }
+    });
+
     return () => {
-      unlisten.then((fn) => fn());
+      unlistenOrganized.then((fn) => fn());
+      unlistenPending.then((fn) => fn());
+      unlistenTransfer.then((fn) => fn());
     };
   }, []);
 
@@ -878,6 +1061,30 @@ async function clearDownloadHistory() {
     }
   }
 
+  // Same-drive moves are a quick rename. Moves to another drive stream in the background
+  // with progress; this resolves with the final path once the job is done.
+  async function moveFile(source: string, destination: string, batchId?: string, conflict?: string): Promise<string | null> {
+    const crossDrive = await invoke<boolean>("needs_transfer", { source, destination });
+    if (!crossDrive) {
+      return invoke<string | null>("move_file", { source, destination, batchId, conflict });
+    }
+
+    const jobId = await invoke<string | null>("start_transfer", { source, destination, mode: "move", batchId, conflict });
+    if (!jobId) return null; // skipped by the conflict policy
+
+    const done = await new Promise<TransferProgress>((resolve) => {
+      const finished = finishedTransfers.current.get(jobId);
+      if (finished) {
+        finishedTransfers.current.delete(jobId);
+        resolve(finished);
+      } else {
+        transferWaiters.current.set(jobId, resolve);
+      }
+    });
+    if (done.state === "completed") return done.destination;
+    throw done.state === "cancelled" ? "Move cancelled" : done.error ?? "Move failed";
+  }
+
   // Single file move (from double-click modal)
   async function handleMoveFile(destination: string) {
     if (!selectedFile) return;
@@ -890,14 +1097,14 @@ async function clearDownloadHistory() {
     console.log("Moving:", sourcePath, "→", destPath);
     
     try {
-      await invoke("move_file", { source: sourcePath, destination: destPath });
+      const finalPath = await moveFile(sourcePath, destPath);
       await invoke("add_recent_destination", { path: destFolder });
       await invoke("add_to_history", {
       name: fileName,
       originalPath: sourcePath,
       size: selectedFile.size,
       action: "moved",
-      destination: destFolder,
+      destination: finalPath ?? destPath,
     });
       setSelectedFile(null);
       loadDownloadFiles();
@@ -917,16 +1124,18 @@ async function clearDownloadHistory() {
     console.log("Moving new download:", newDownload.path, "→", destPath);
     
     try {
-      await invoke("move_file", { source: newDownload.path, destination: destPath });
+      const finalPath = await moveFile(newDownload.path, destPath);
       await invoke("add_recent_destination", { path: destFolder });
       await invoke("add_to_history", {
       name: newDownload.name,
       originalPath: newDownload.path,
       size: newDownload.size,
       action: "moved",
-      destination: destFolder,
+      destination: finalPath ?? destPath,
+      detectedAt: newDownload.detected_at,
     });
       setNewDownload(null);
+      await invoke("resolve_pending", { id: newDownload.id, action: "moved" });
       loadDownloadFiles();
     } catch (err) {
       console.error("Failed to move file:", err);
@@ -934,22 +1143,52 @@ async function clearDownloadHistory() {
     }
   }
 
-  // Delete single file
-  async function handleDeleteFile(file: FileEntry | NewFileEvent) {
-    const confirmed = window.confirm(`Send "${file.name}" to Recycle Bin?`);
-    if (!confirmed) return;
+  // Leave a new download where it is
+  async function handleKeepNewDownload() {
+    if (!newDownload) return;
+    setNewDownload(null);
+    try {
+      await invoke("resolve_pending", { id: newDownload.id, action: "kept" });
+    } catch (err) {
+      console.error("Failed to keep file:", err);
+    }
+  }
+
+  // Ask again about a new download in an hour
+  async function handleSnoozeNewDownload() {
+    if (!newDownload) return;
+    const until = new Date(Date.now() + 60 * 60 * 1000);
+    const pad = (n: number) => String(n).padStart(2, "0");
+    const timestamp = `${until.getFullYear()}-${pad(until.getMonth() + 1)}-${pad(until.getDate())} ` +
+      `${pad(until.getHours())}:${pad(until.getMinutes())}:${pad(until.getSeconds())}`;
+    setNewDownload(null);
+    try {
+      await invoke("snooze_pending", { id: newDownload.id, until: timestamp });
+    } catch (err) {
+      console.error("Failed to snooze file:", err);
+    }
+  }
+
+  // Delete single file. Discarding a known re-download skips the confirmation.
+  async function handleDeleteFile(file: FileEntry | PendingDownload, confirmFirst = true) {
+    if (confirmFirst && !window.confirm(`Send "${file.name}" to Recycle Bin?`)) return;
     
     try {
-      await invoke("delete_file", { path: file.path });
+      const trashId = await invoke<string | null>("delete_file", { path: file.path });
       await invoke("add_to_history", {
       name: file.name,
       originalPath: file.path,
       size: file.size,
       action: "deleted",
       destination: null,
+      detectedAt: "detected_at" in file ? file.detected_at : null,
+      trashId,
     });
       setSelectedFile(null);
       setNewDownload(null);
+      if ("id" in file) {
+        await invoke("resolve_pending", { id: file.id, action: "deleted" });
+      }
       loadDownloadFiles();
     } catch (err) {
       console.error("Failed to delete file:", err);
@@ -1075,16 +1314,19 @@ function handleKeyDown(event: React.KeyboardEvent) {
     );
     if (!confirmed) return;
     
+    // Shared batch id so the whole delete undoes as one step
+    const batchId = crypto.randomUUID();
     let successCount = 0;
     for (const file of selectedFiles) {
       try {
-        await invoke("delete_file", { path: file.path });
+        const trashId = await invoke<string | null>("delete_file", { path: file.path, batchId });
         await invoke("add_to_history", {
         name: file.name,
         originalPath: file.path,
         size: file.size,
         action: "deleted",
         destination: null,
+        trashId,
       });
         successCount++;
       } catch (err) {
@@ -1102,18 +1344,35 @@ function handleKeyDown(event: React.KeyboardEvent) {
     if (selectedFiles.length === 0) return;
     
     const destFolder = destination.endsWith("\\") ? destination : destination + "\\";
+
+    // Ask once for the whole batch instead of failing file by file
+    let conflict = "fail";
+    const conflicts = await invoke<unknown[]>("check_conflicts", {
+      sources: selectedFiles.map((f) => f.path),
+      destinationFolder: destFolder,
+    });
+    if (conflicts.length > 0) {
+      const keepBoth = window.confirm(
+        `${conflicts.length} file(s) already exist in the destination.\n\nOK to keep both copies, Cancel to skip them.`
+      );
+      conflict = keepBoth ? "keep_both" : "skip";
+    }
+
+    // Shared batch id so the whole move undoes as one step
+    const batchId = crypto.randomUUID();
     let successCount = 0;
     
     for (const file of selectedFiles) {
       const destPath = `${destFolder}${file.name}`;
       try {
-        await invoke("move_file", { source: file.path, destination: destPath });
+        const finalPath = await moveFile(file.path, destPath, batchId, conflict);
+        if (!finalPath) continue; // skipped by the conflict policy
         await invoke("add_to_history", {
         name: file.name,
         originalPath: file.path,
         size: file.size,
         action: "moved",
-        destination: destFolder,
+        destination: finalPath, // differs from destPath when both copies were kept
       });
         successCount++;
       } catch (err) {
@@ -1187,6 +1446,19 @@ function handleKeyDown(event: React.KeyboardEvent) {
 >
    <Settings className="w-5 h-5 text-gray-300" />
 </button>
+{/* Inbox button - opens the oldest queued download */}
+{pending.length > 0 && (
+  <button
+    onClick={() => setNewDownload(pending[0])}
+    className="flex items-center gap-2 px-3 py-2 bg-gray-700 hover:bg-gray-600 rounded-lg text-gray-300 transition-all"
+    title="Downloads waiting for a decision"
+  >
+    <Inbox className="w-5 h-5" />
+    <span className="bg-red-500 text-white text-xs px-2 py-0.5 rounded-full">
+      {pending.length}
+    </span>
+  </button>
+)}
 {/* History button */}
 <button
   onClick={() => {
@@ -1376,6 +1648,9 @@ function handleKeyDown(event: React.KeyboardEvent) {
           onClose={() => setNewDownload(null)}
           onMove={handleMoveNewDownload}
           onDelete={() => handleDeleteFile(newDownload)}
+          onDiscard={() => handleDeleteFile(newDownload, false)}
+          onKeep={handleKeepNewDownload}
+          onSnooze={handleSnoozeNewDownload}
         />
       )}
       {/* History modal */}
@@ -1428,14 +1703,22 @@ function handleKeyDown(event: React.KeyboardEvent) {
                     </div>
                   </div>
                   <div className={`text-xs px-2 py-1 rounded ${
-                    entry.action === "moved" 
+                    entry.action === "moved" || entry.action === "renamed"
                       ? "bg-blue-900/50 text-blue-300" 
                       : entry.action === "deleted"
                       ? "bg-red-900/50 text-red-300"
                       : "bg-gray-700 text-gray-300"
                   }`}>
-                    {entry.action === "moved" ? "Moved" : entry.action === "deleted" ? "Deleted" : "Kept"}
+                    {entry.action === "moved" ? "Moved" : entry.action === "renamed" ? "Renamed" : entry.action === "deleted" ? "Deleted" : entry.action === "restored" ? "Restored" : "Kept"}
                   </div>
+                  {entry.action === "deleted" && entry.trash_id && trashIds.has(entry.trash_id) && (
+                    <button
+                      onClick={() => restoreFromHistory(entry)}
+                      className="text-xs px-2 py-1 bg-gray-700 hover:bg-blue-600 rounded text-white transition-all"
+                    >
+                      Restore
+                    </button>
+                  )}
                 </div>
                 {entry.destination && (
                   <div className="mt-2 text-xs text-gray-400 truncate">
@@ -1611,6 +1894,8 @@ function handleKeyDown(event: React.KeyboardEvent) {
           onMove={handleBulkMove}
         />
       )}
+
+      <TransferPanel transfers={transfers} />
     </div>
   );
 }